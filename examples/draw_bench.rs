@@ -0,0 +1,75 @@
+//! Compares drawing every cube with its own draw call against one
+//! instanced call for all of them. Run from the repository root, so the
+//! shaders are found, with `cargo run --release --example draw_bench`.
+
+use std::time::Instant;
+use tetris::graphics::{shapes, Camera, MeshRef, RenderObject, Renderer};
+
+const FRAMES: u32 = 200;
+
+// A grid of `count` cubes laid out like board cells
+fn cubes(mesh: MeshRef, count: usize) -> Vec<RenderObject> {
+    let columns = (count as f32).sqrt().ceil() as usize;
+    (0 .. count).map(|i| {
+        let (x, y) = ((i % columns) as f32, (i / columns) as f32);
+        let mut obj = RenderObject::new(mesh);
+        obj.trans().move_to(0.5 - x / columns as f32, 0.5 - y / columns as f32, 0.);
+        obj.trans().set_uniform_scale(0.5 / columns as f32);
+        obj.rgb(x / columns as f32, y / columns as f32, 0.5);
+        obj.pattern((i % 8) as i32);
+        obj
+    }).collect()
+}
+
+fn main() {
+    let events_loop = glium::glutin::EventsLoop::new();
+    let window = glium::glutin::WindowBuilder::new()
+        .with_title("draw_bench");
+    let context = glium::glutin::ContextBuilder::new()
+        .with_depth_buffer(24)
+        .with_vsync(false);
+    let display = glium::Display::new(window, context, &events_loop).unwrap();
+
+    let mut renderer = Renderer::new(&display).unwrap();
+    renderer.use_program("shaders/vertex.vert", "shaders/fragment.frag")
+        .unwrap();
+    let cube_mesh = renderer.load_mesh_with(&String::from("unit_cube"), &shapes::make_unit_cube)
+        .unwrap();
+
+    let mut camera = Camera::default();
+    camera.aspect(1.);
+    camera.set_far(8.);
+    camera.set_near(1.);
+    camera.position(0., 0., -2.);
+
+    for count in [200, 600, 2_000, 10_000].iter() {
+        let mut objects = cubes(cube_mesh, *count);
+        for batched in [false, true].iter() {
+            let mut draw = |renderer: &mut Renderer, objects: &mut Vec<RenderObject>| {
+                if *batched {
+                    renderer.render(objects, &mut camera)
+                } else {
+                    renderer.render_unbatched(objects, &mut camera)
+                }
+            };
+            // Warm up so buffers are allocated before timing
+            for _ in 0 .. 10 {
+                draw(&mut renderer, &mut objects);
+            }
+            display.finish();
+
+            let start = Instant::now();
+            for _ in 0 .. FRAMES {
+                draw(&mut renderer, &mut objects);
+            }
+            display.finish();
+            let per_frame = start.elapsed() / FRAMES;
+            println!(
+                "{:>6} cubes {:>10}: {:>8.3} ms per frame",
+                count,
+                if *batched { "instanced" } else { "unbatched" },
+                per_frame.as_micros() as f64 / 1000.
+            );
+        }
+    }
+}
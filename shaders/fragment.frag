@@ -1,8 +1,66 @@
 #version 330
 
+in vec3 world_position;
+in vec3 world_normal;
+in vec2 face_uv;
+in vec4 base_color;
+// 0 draws the plain color, 1 to 7 a glyph for the piece types I to Z
+flat in int base_pattern;
+
 out vec4 color;
-uniform vec4 rgba_color;
+uniform vec3 eye_position;
+
+// Set by the renderer, see `graphics::light::Light`
+layout(std140) uniform Light {
+  vec4 light_direction;
+  vec4 light_color;
+  vec4 ambient_color;
+  // Shininess in w
+  vec4 specular_color;
+};
+
+bool glyph(int id, vec2 uv) {
+  vec2 a = abs(uv);
+  if (id == 1) {
+    // I: horizontal bars
+    return fract(uv.y * 2.0) < 0.35;
+  } else if (id == 2) {
+    // J: dot
+    return length(uv) < 0.45;
+  } else if (id == 3) {
+    // L: diagonal stripes
+    return fract((uv.x + uv.y) * 1.5) < 0.35;
+  } else if (id == 4) {
+    // O: ring
+    return max(a.x, a.y) > 0.55 && max(a.x, a.y) < 0.8;
+  } else if (id == 5) {
+    // S: checkers
+    return mod(floor(uv.x * 2.0) + floor(uv.y * 2.0), 2.0) < 0.5;
+  } else if (id == 6) {
+    // T: plus
+    return min(a.x, a.y) < 0.15 && max(a.x, a.y) < 0.7;
+  } else if (id == 7) {
+    // Z: cross
+    return abs(a.x - a.y) < 0.15 && a.x < 0.7;
+  }
+  return false;
+}
 
 void main() {
-  color = rgba_color;
+  vec3 base = base_color.rgb;
+  if (glyph(base_pattern, face_uv)) {
+    base *= 0.3;
+  }
+
+  // Blinn-Phong: diffuse from the light's angle, specular from the half
+  // way vector between light and eye
+  vec3 n = normalize(world_normal);
+  vec3 l = normalize(-light_direction.xyz);
+  vec3 h = normalize(l + normalize(eye_position - world_position));
+  float diffuse = max(dot(n, l), 0.0);
+  float specular = diffuse > 0.0 ? pow(max(dot(n, h), 0.0), specular_color.w) : 0.0;
+
+  vec3 lit = base * (ambient_color.rgb + diffuse * light_color.rgb)
+    + specular * specular_color.rgb;
+  color = vec4(lit, base_color.a);
 }
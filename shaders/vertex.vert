@@ -1,11 +1,30 @@
 #version 330
 
 in vec3 position;
+in vec3 normal;
+in vec2 uv;
+
+// Per instance, see `graphics::core::Instance`
+in mat4 model_mat;
+in vec4 rgba_color;
+in int pattern;
 
 uniform mat4 camera_mat;
-uniform mat4 model_mat;
 
+out vec3 world_position;
+out vec3 world_normal;
+// Position on the face, the fragment shader draws the glyph with it
+out vec2 face_uv;
+out vec4 base_color;
+flat out int base_pattern;
 
 void main() {
-  gl_Position = camera_mat * model_mat * vec4(position.xyz, 1.0);
+  vec4 world = model_mat * vec4(position, 1.0);
+  world_position = world.xyz;
+  // Scaling is not uniform, so normals need the inverse transpose
+  world_normal = transpose(inverse(mat3(model_mat))) * normal;
+  face_uv = uv;
+  base_color = rgba_color;
+  base_pattern = pattern;
+  gl_Position = camera_mat * world;
 }
@@ -1,3 +1,24 @@
+use crate::graphics::ray::Ray;
+
+/// How the view volume is mapped onto the screen.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Projection {
+    /// Things further away are drawn smaller, using the camera's `fovy`
+    /// and `aspect`.
+    Perspective,
+    /// Parallel lines stay parallel, the view volume is a box `width` by
+    /// `height` around the line of sight. Good for a flat 2D look.
+    Orthographic {
+        /// Width of the view volume.
+        width: f32,
+        /// Height of the view volume.
+        height: f32
+    },
+}
+
+/// A camera looking from `position` along `at`, building the matrix the
+/// vertex shader takes. Parts of the matrix are only rebuilt after the
+/// settings they depend on change.
 pub struct Camera {
     uniform_matrix: nalgebra::Matrix4<f32>,
 
@@ -14,6 +35,9 @@ pub struct Camera {
     fovy: f32,
     near: f32,
     far: f32,
+    projection: Projection,
+    // Width over height the viewport is kept at, with bars around it
+    letterbox: Option<f64>,
 
     position: nalgebra::Vector3<f32>,
     up: nalgebra::Vector3<f32>,
@@ -37,6 +61,8 @@ impl Default for Camera {
             fovy: 0.78,  // 45 degrees
             near: 10.0,  // Completely arbitary
             far: 100.,  // Completely arbitary
+            projection: Projection::Perspective,
+            letterbox: Option::None,
 
             position: nalgebra::Vector3::new(0.,0.,0.),
             up: nalgebra::Vector3::new(0.,1.,0.),
@@ -48,11 +74,92 @@ impl Default for Camera {
 
 impl Camera {
 
+    /// The matrix taking world space to clip space, as the shader takes it.
     pub fn as_primitive(&mut self) -> [[f32; 4]; 4] {
+        *self.matrix().as_ref()
+    }
+
+    fn matrix(&mut self) -> nalgebra::Matrix4<f32> {
         if self.anything_changed() {
             self.update_matrix();
         };
-        *self.uniform_matrix.as_ref()
+        self.uniform_matrix
+    }
+
+    /// Where the camera sits in world space.
+    pub fn eye(&self) -> nalgebra::Vector3<f32> {
+        nalgebra::Rotation3::look_at_rh(&self.at, &self.up).inverse() * -self.position
+    }
+
+    /// The part of a window of size `window` that is drawn to, as left,
+    /// top, width and height in the same units. The whole window unless a
+    /// letterbox is set, then the largest centered area of that shape.
+    pub fn viewport(&self, (width, height): (f64, f64)) -> (f64, f64, f64, f64) {
+        match self.letterbox {
+            Option::Some(aspect) if width > aspect * height => {
+                let fitted = aspect * height;
+                ((width - fitted) / 2., 0., fitted, height)
+            },
+            Option::Some(aspect) => {
+                let fitted = width / aspect;
+                (0., (height - fitted) / 2., width, fitted)
+            },
+            Option::None => (0., 0., width, height)
+        }
+    }
+
+    /// The ray through the point `screen` in pixels from the top left of a
+    /// window of size `window`. It starts on the near plane and reaches
+    /// the far plane at `t = 1`. `None` if the view can't be inverted.
+    pub fn unproject(
+        &mut self,
+        (x, y): (f64, f64),
+        window: (f64, f64)
+    ) -> Option<Ray> {
+        let inverse = self.matrix().try_inverse()?;
+        let (left, top, width, height) = self.viewport(window);
+        let ndc_x = (2. * (x - left) / width - 1.) as f32;
+        let ndc_y = (1. - 2. * (y - top) / height) as f32;
+        let unproject = |depth: f32| {
+            let p = inverse * nalgebra::Vector4::new(ndc_x, ndc_y, depth, 1.);
+            nalgebra::Vector3::new(p[0] / p[3], p[1] / p[3], p[2] / p[3])
+        };
+        let near = unproject(-1.);
+        Option::Some(Ray::new(near, unproject(1.) - near))
+    }
+
+    /// Pixels from the top left of a window of size `window` where `world`
+    /// is drawn. `None` for points behind the camera.
+    pub fn project(
+        &mut self,
+        world: &nalgebra::Vector3<f32>,
+        window: (f64, f64)
+    ) -> Option<(f64, f64)> {
+        let clip = self.matrix() * nalgebra::Vector4::new(world[0], world[1], world[2], 1.);
+        if clip[3] <= 0. {
+            return Option::None
+        }
+        let (left, top, width, height) = self.viewport(window);
+        let (ndc_x, ndc_y) = ((clip[0] / clip[3]) as f64, (clip[1] / clip[3]) as f64);
+        Option::Some((left + (ndc_x + 1.) / 2. * width, top + (1. - ndc_y) / 2. * height))
+    }
+
+    /// The point on the plane at depth `z` under the cursor, given in pixels
+    /// from the top left of a window of size `window`. `None` if the
+    /// plane is parallel to the line of sight or outside the view volume.
+    pub fn unproject_onto_plane(
+        &mut self,
+        cursor: (f64, f64),
+        window: (f64, f64),
+        z: f32
+    ) -> Option<nalgebra::Vector3<f32>> {
+        let ray = self.unproject(cursor, window)?;
+        let plane = nalgebra::Vector3::new(0., 0., z);
+        let t = ray.intersect_plane(&plane, &nalgebra::Vector3::new(0., 0., 1.))?;
+        if t > 1. {
+            return Option::None
+        }
+        Option::Some(ray.at(t))
     }
 
     fn  anything_changed(&self) -> bool {
@@ -73,9 +180,14 @@ impl Camera {
 
     fn update_matrix(&mut self) -> &Camera{
         if self.changed_proj {
-            self.proj_cached = nalgebra::Perspective3::new(
-                self.aspect, self.fovy, self.near, self.far
-            ).to_homogeneous();
+            self.proj_cached = match self.projection {
+                Projection::Perspective => nalgebra::Perspective3::new(
+                    self.aspect, self.fovy, self.near, self.far
+                ).to_homogeneous(),
+                Projection::Orthographic { width, height } => nalgebra::Orthographic3::new(
+                    -width / 2., width / 2., -height / 2., height / 2., self.near, self.far
+                ).to_homogeneous(),
+            };
         }
 
         if self.changed_trans {
@@ -88,9 +200,13 @@ impl Camera {
         }
 
         self.uniform_matrix = self.proj_cached * self.trans_cached * self.look_at_cached;
+        self.changed_proj = false;
+        self.changed_trans = false;
+        self.changed_look_at = false;
         self
     }
 
+    /// Sets the aspect ratio, height over width.
     pub fn aspect(&mut self, aspect: f32) -> &Camera{
         self.aspect = aspect;
         self.set_changed_proj();
@@ -98,25 +214,49 @@ impl Camera {
     }
 
 
+    /// Sets the aspect ratio from a window size, width and height.
     pub fn aspect_of(&mut self, (x,y): (f64,f64)) -> &Camera {
         self.aspect((y/x) as f32)
     }
 
+    /// Sets the vertical field of view, in radians.
     pub fn fovy(&mut self, fovy: f32) {
         self.fovy = fovy;
         self.set_changed_proj();
     }
+    /// Sets the distance to the near clipping plane.
     pub fn set_near(&mut self, near: f32)  -> &Camera {
         self.near = near;
         self.set_changed_proj();
         self
     }
+    /// Sets the distance to the far clipping plane.
     pub fn set_far(&mut self, far: f32) -> &Camera {
         self.far = far;
         self.set_changed_proj();
         self
     }
 
+    /// The projection in use.
+    pub fn projection(&self) -> Projection {
+        self.projection
+    }
+
+    /// Switches to `projection`.
+    pub fn set_projection(&mut self, projection: Projection) -> &Camera {
+        self.projection = projection;
+        self.set_changed_proj();
+        self
+    }
+
+    /// Keeps the viewport at `aspect`, width over height, whatever the
+    /// window's shape. `None` fills the window.
+    pub fn set_letterbox(&mut self, aspect: Option<f64>) -> &Camera {
+        self.letterbox = aspect;
+        self
+    }
+
+    /// Turns the view around the axis across it, pointing it up or down.
     pub fn horizontal_rotate(&mut self, angle: f32) -> &Camera {
         let rot_vec = self.at.cross(&self.up);
         self.at = nalgebra::Rotation3::from_axis_angle(
@@ -126,6 +266,7 @@ impl Camera {
         self
     }
 
+    /// Turns the view around the world's y axis.
     pub fn vertical_rotate(&mut self, angle: f32) -> &Camera {
         let rot_vec = nalgebra::Vector3::new(0., 1., 0.);
         self.at = nalgebra::Rotation3::from_axis_angle(
@@ -135,63 +276,160 @@ impl Camera {
         self
     }
 
+    /// Same as `move_to`.
     pub fn position(&mut self, x: f32, y: f32, z: f32) -> &Camera {
         self.move_to(x,y,z)
     }
+    /// Moves the camera to `(x, y, z)`.
     pub fn move_to(&mut self, x: f32, y: f32, z: f32) -> &Camera {
         self.position = nalgebra::Vector3::new(x,y,z);
         self.set_changed_trans();
         self
     }
 
+    /// Same as `move_to_vec`.
     pub fn position_ve(&mut self, v: nalgebra::Vector3<f32>) -> &Camera {
         self.move_to_vec(v)
     }
 
+    /// Moves the camera to `v`.
     pub fn move_to_vec(&mut self, v: nalgebra::Vector3<f32>) -> &Camera {
         self.position = v;
         self.set_changed_trans();
         self
     }
 
+    /// Moves the camera by `v`.
     pub fn add_position(&mut self, v: &nalgebra::Vector3<f32>) -> &Camera{
         self.position += v;
         self.set_changed_trans();
         self
     }
 
+    /// Moves the camera `d` times the view direction.
     pub fn move_forwards(&mut self, d: f32) -> &Camera {
         let a = d * self.at;
         self.add_position(&a);
         self
     }
 
+    /// Moves the camera `d` times the view direction backwards.
     pub fn move_backwards(&mut self, d: f32) -> &Camera {
         self.move_forwards(-d);
         self
     }
 
+    /// Moves the camera `d` times the direction across the view.
     pub fn move_sideways(&mut self, d: f32) -> &Camera {
         let a = self.at.cross(&self.up) * d;
         self.add_position(&a);
         self
     }
 
+    /// Moves the camera by the direction across the view, `_d` isn't used yet.
     pub fn move_up(&mut self, _d: f32) -> &Camera {
         let a = self.at.cross(&self.up);
         self.add_position(&a);
         self
     }
 
+    /// Sets which way is up on the screen.
     pub fn up(&mut self, up: nalgebra::Vector3<f32>) -> &Camera {
         self.up = up;
         self.set_changed_look_at();
         self
     }
 
+    /// Sets the direction the camera looks in.
     pub fn set_look_at(&mut self, at: nalgebra::Vector3<f32>) -> &Camera {
         self.at = at;
         self.set_changed_look_at();
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: (f64, f64) = (200., 100.);
+
+    fn close(a: (f64, f64), b: (f64, f64)) -> bool {
+        (a.0 - b.0).abs() < 1e-3 && (a.1 - b.1).abs() < 1e-3
+    }
+
+    // Looking down +z from the origin, with the view volume 4 wide and 2
+    // tall between z = 1 and 10
+    fn flat() -> Camera {
+        let mut camera = Camera::default();
+        camera.set_near(1.);
+        camera.set_far(10.);
+        camera.set_projection(Projection::Orthographic { width: 4., height: 2. });
+        camera
+    }
+
+    fn perspective() -> Camera {
+        let mut camera = Camera::default();
+        camera.aspect_of(WINDOW);
+        camera.set_near(1.);
+        camera.set_far(10.);
+        camera.move_to(0.5, -0.25, 0.);
+        camera
+    }
+
+    #[test]
+    fn orthographic_projection_maps_the_view_box_onto_the_window() {
+        let mut camera = flat();
+        let project = |camera: &mut Camera, x, y| {
+            camera.project(&nalgebra::Vector3::new(x, y, 5.), WINDOW).unwrap()
+        };
+        assert!(close(project(&mut camera, 0., 0.), (100., 50.)));
+        // Looking down +z puts +x on the left of the screen
+        assert!(close(project(&mut camera, 2., 1.), (0., 0.)));
+        assert!(close(project(&mut camera, -1., -0.5), (150., 75.)));
+    }
+
+    #[test]
+    fn letterboxing_centers_the_viewport() {
+        let mut camera = flat();
+        camera.set_letterbox(Option::Some(1.));
+        assert_eq!(camera.viewport(WINDOW), (50., 0., 100., 100.));
+        let corner = camera.project(&nalgebra::Vector3::new(2., 1., 5.), WINDOW).unwrap();
+        assert!(close(corner, (50., 0.)));
+    }
+
+    #[test]
+    fn unprojecting_a_projected_point_gives_it_back() {
+        for camera in [&mut flat(), &mut perspective()] {
+            for point in [(0., 0., 5.), (0.7, -0.3, 2.), (-1.2, 0.4, 9.)] {
+                let world = nalgebra::Vector3::new(point.0, point.1, point.2);
+                let screen = camera.project(&world, WINDOW).unwrap();
+                let back = camera.unproject_onto_plane(screen, WINDOW, point.2).unwrap();
+                assert!((back - world).norm() < 1e-3, "{:?} came back as {:?}", world, back);
+
+                let ray = camera.unproject(screen, WINDOW).unwrap();
+                let t = ray.intersect_plane(&world, &nalgebra::Vector3::new(0., 0., 1.)).unwrap();
+                assert!(t <= 1., "{:?} is past the far plane", world);
+            }
+        }
+    }
+
+    #[test]
+    fn the_matrix_is_only_rebuilt_after_a_change() {
+        let mut camera = perspective();
+        let before = camera.as_primitive();
+        assert!(!camera.anything_changed());
+        camera.move_to(0., 0., 1.);
+        assert!(camera.anything_changed());
+        assert_ne!(camera.as_primitive(), before);
+        assert!(!camera.anything_changed());
+    }
+
+    #[test]
+    fn points_behind_the_camera_or_planes_outside_the_view_are_none() {
+        let mut camera = perspective();
+        assert_eq!(camera.project(&nalgebra::Vector3::new(0., 0., -3.), WINDOW), Option::None);
+        assert_eq!(camera.unproject_onto_plane((100., 50.), WINDOW, 20.), Option::None);
+        assert_eq!(camera.unproject_onto_plane((100., 50.), WINDOW, -2.), Option::None);
+    }
+}
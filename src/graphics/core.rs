@@ -3,57 +3,106 @@ use std::path::Path;
 use glium::program::Program;
 use std::fs::File;
 
-use::std::error::Error;
 
+/// Why the buffers of a `Mesh` or a `Renderer` couldn't be made.
 #[derive(Debug)]
 pub enum BufferCreationError {
+    /// The index buffer was rejected.
     IndexError(glium::index::BufferCreationError),
-    VertexError(glium::vertex::BufferCreationError)
+    /// The vertex buffer was rejected.
+    VertexError(glium::vertex::BufferCreationError),
+    /// The uniform buffer was rejected.
+    UniformError(glium::buffer::BufferCreationError)
 }
 
-impl std::error::Error for BufferCreationError {
-    fn description(&self) -> &str {
-        match self {
-            BufferCreationError::IndexError(e) => e.description(),
-            BufferCreationError::VertexError(e) => e.description()
-        }
-    }
-}
+impl std::error::Error for BufferCreationError {}
 
 impl std::fmt::Display for BufferCreationError {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
-
-        write!(fmt, "{}", self.description())
+        match self {
+            BufferCreationError::IndexError(e) => write!(fmt, "{}", e),
+            BufferCreationError::VertexError(e) => write!(fmt, "{}", e),
+            BufferCreationError::UniformError(e) => write!(fmt, "{}", e)
+        }
     }
 }
 
 
+/// A corner of a mesh as the vertex shader sees it.
 #[derive(Copy, Clone)]
 pub struct Vertex {
-    pub position : [f32; 3]
+    /// Position in model space.
+    pub position : [f32; 3],
+    /// Which way the surface faces, for lighting.
+    pub normal : [f32; 3],
+    /// Position on the face, -1 to 1 on both axes, for the glyphs.
+    pub uv : [f32; 2],
 }
 
 impl Vertex {
+    /// A point on the z = 0 plane.
     pub const fn new_2d(x: f32, y: f32) -> Vertex {
-        Vertex{position:[x, y, 0.]}
+        Vertex::new(x, y, 0.)
     }
+    /// A vertex facing the default camera, with its x and y as UVs.
     pub const fn new(x: f32, y: f32, z: f32) -> Vertex {
-        Vertex{position:[x, y, z]}
+        Vertex::with_normal([x, y, z], [0., 0., -1.], [x, y])
+    }
+    /// A vertex with every attribute given.
+    pub const fn with_normal(position: [f32; 3], normal: [f32; 3], uv: [f32; 2]) -> Vertex {
+        Vertex{position, normal, uv}
     }
 }
 
 
-glium::implement_vertex!(Vertex, position);
+/// What changes between copies of a mesh drawn in a single call.
+#[derive(Copy, Clone)]
+pub struct Instance {
+    pub model_mat: [[f32; 4]; 4],
+    pub rgba_color: [f32; 4],
+    // Glyph the fragment shader draws on every face, 0 for none
+    pub pattern: i32,
+}
 
 
+// The macro still zero initializes with `mem::uninitialized`
+#[allow(deprecated)]
+mod vertex_formats {
+    use super::{Instance, Vertex};
+    glium::implement_vertex!(Vertex, position, normal, uv);
+    glium::implement_vertex!(Instance, model_mat, rgba_color, pattern);
+}
+
+
+/// Vertices and the triangles between them, uploaded to the GPU.
 pub struct Mesh {
     index_buffer:  glium::IndexBuffer<u16>,
-    vertex_buffer: glium::VertexBuffer<Vertex>
+    vertex_buffer: glium::VertexBuffer<Vertex>,
+    // Corners of the box around every vertex, for picking
+    min: nalgebra::Vector3<f32>,
+    max: nalgebra::Vector3<f32>,
+}
+
+// Smallest and largest coordinate on every axis
+fn bounds(vertices: &[Vertex]) -> (nalgebra::Vector3<f32>, nalgebra::Vector3<f32>) {
+    if vertices.is_empty() {
+        return (nalgebra::Vector3::new(0., 0., 0.), nalgebra::Vector3::new(0., 0., 0.))
+    }
+    let mut min = nalgebra::Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+    let mut max = -min;
+    for vertex in vertices {
+        for axis in 0 .. 3 {
+            min[axis] = min[axis].min(vertex.position[axis]);
+            max[axis] = max[axis].max(vertex.position[axis]);
+        }
+    }
+    (min, max)
 }
 
 
 impl Mesh {
 
+    /// Draws the mesh once.
     pub fn draw<S, U>(
         &self,
         surface: &mut S,
@@ -71,14 +120,41 @@ impl Mesh {
         )
     }
 
+    /// Draws the mesh once for every instance in `instances`, a buffer of
+    /// `Instance`s.
+    pub fn draw_instanced<S, U>(
+        &self,
+        surface: &mut S,
+        program: &glium::Program,
+        instances: glium::vertex::PerInstance,
+        uniforms: &U,
+        params: &glium::DrawParameters
+    ) -> Result<(), glium::DrawError>
+    where S: glium::Surface, U: glium::uniforms::Uniforms  {
+        surface.draw(
+            (&self.vertex_buffer, instances),
+            &self.index_buffer,
+            program,
+            uniforms,
+            params
+        )
+    }
+
+    /// Corners of the box around the mesh in model space.
+    pub fn bounds(&self) -> (nalgebra::Vector3<f32>, nalgebra::Vector3<f32>) {
+        (self.min, self.max)
+    }
+
+    /// Uploads `vertices` and the triangles that `indices` make of them, three
+    /// indices a triangle.
     pub fn new<F> (
         facade: &F,
         indices: &[u16],
         vertices: &[Vertex]
     ) -> std::result::Result<Mesh, BufferCreationError>
     where F: Facade {
-        glium::VertexBuffer::new(facade, vertices)
-            .or_else(|e|{Result::Err(BufferCreationError::VertexError(e))})
+        let (min, max) = bounds(vertices);
+        glium::VertexBuffer::new(facade, vertices).map_err(BufferCreationError::VertexError)
             .and_then(|vb|{
                 glium::index::IndexBuffer::new(
                     facade,
@@ -86,11 +162,12 @@ impl Mesh {
                     indices)
                     .map_err(BufferCreationError::IndexError)
                     .map(|ib|{
-                        Mesh{index_buffer: ib, vertex_buffer: vb}
+                        Mesh{index_buffer: ib, vertex_buffer: vb, min, max}
                 })
         })
     }
 
+    /// Uploads `vertices`, every three of them a triangle.
     pub fn new_triangles<F> (
         facade: &F,
         vertices: &[Vertex]
@@ -104,25 +181,23 @@ impl Mesh {
 
 
 
+/// Why a shader program couldn't be made.
 #[derive(Debug)]
 pub enum ProgramCreationError {
+    /// A shader file couldn't be read.
     IOError(std::io::Error),
+    /// The shaders didn't compile or link.
     ShaderError(glium::ProgramCreationError)
 }
 
-impl std::error::Error for ProgramCreationError {
-    fn description(&self) -> &str {
-        match self {
-            ProgramCreationError::IOError(e) => e.description(),
-            ProgramCreationError::ShaderError(e) => e.description()
-        }
-    }
-}
+impl std::error::Error for ProgramCreationError {}
 
 impl std::fmt::Display for ProgramCreationError {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
-
-        write!(fmt, "{}", self.description())
+        match self {
+            ProgramCreationError::IOError(e) => write!(fmt, "{}", e),
+            ProgramCreationError::ShaderError(e) => write!(fmt, "{}", e)
+        }
     }
 }
 
@@ -0,0 +1,57 @@
+use glium::implement_uniform_block;
+
+/// The directional light every object is shaded with, Blinn-Phong style.
+/// Colors are linear RGB from 0 to 1.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Light {
+    /// The way the light travels, towards the board for it to be lit.
+    pub direction: [f32; 3],
+    /// Color of the diffuse light.
+    pub color: [f32; 3],
+    /// Added to every surface whatever way it faces.
+    pub ambient: [f32; 3],
+    /// Color of the highlights.
+    pub specular: [f32; 3],
+    /// How tight the highlights are, higher is shinier.
+    pub shininess: f32,
+}
+
+impl Default for Light {
+    fn default() -> Light {
+        // From the top left behind the default camera
+        Light {
+            direction: [-0.4, -0.6, 1.],
+            color: [0.8, 0.8, 0.8],
+            ambient: [0.3, 0.3, 0.3],
+            specular: [0.4, 0.4, 0.4],
+            shininess: 32.,
+        }
+    }
+}
+
+// The `Light` uniform block of the fragment shader. Only vec4s so std140
+// adds no padding, shininess rides along in the specular color
+#[derive(Copy, Clone)]
+pub(crate) struct LightBlock {
+    light_direction: [f32; 4],
+    light_color: [f32; 4],
+    ambient_color: [f32; 4],
+    specular_color: [f32; 4],
+}
+
+implement_uniform_block!(LightBlock, light_direction, light_color, ambient_color, specular_color);
+
+impl From<&Light> for LightBlock {
+    fn from(light: &Light) -> LightBlock {
+        let [x, y, z] = light.direction;
+        let [r, g, b] = light.color;
+        let [ar, ag, ab] = light.ambient;
+        let [sr, sg, sb] = light.specular;
+        LightBlock {
+            light_direction: [x, y, z, 0.],
+            light_color: [r, g, b, 1.],
+            ambient_color: [ar, ag, ab, 1.],
+            specular_color: [sr, sg, sb, light.shininess],
+        }
+    }
+}
@@ -1,4 +1,16 @@
-pub mod core;
+//! OpenGL rendering of lit, instanced meshes, with a camera that can pick
+//! what's under the cursor.
+
+pub(crate) mod core;
 pub mod shapes;
-pub mod camera;
-pub mod renderer;
+pub(crate) mod camera;
+pub(crate) mod ray;
+pub(crate) mod light;
+pub(crate) mod renderer;
+
+pub use crate::graphics::core::{BufferCreationError, Mesh, ProgramCreationError, Vertex};
+pub use crate::graphics::camera::{Camera, Projection};
+pub use crate::graphics::ray::Ray;
+pub use crate::graphics::light::Light;
+pub use crate::graphics::renderer::{DrawState, MeshRef, MeshStore, ModelTrans};
+pub use crate::graphics::renderer::{RenderObject, Renderer};
@@ -0,0 +1,138 @@
+use nalgebra::Vector3;
+
+/// A half line from `origin` along `direction`, like the one `Camera`
+/// casts through a cursor. Points on it are `origin + t * direction` for
+/// `t >= 0`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ray {
+    /// Where the ray starts.
+    pub origin: Vector3<f32>,
+    /// The way it goes, not normalized.
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    /// The ray from `origin` along `direction`.
+    pub fn new(origin: Vector3<f32>, direction: Vector3<f32>) -> Ray {
+        Ray {
+            origin,
+            direction,
+        }
+    }
+
+    /// The point at `t` along the ray.
+    pub fn at(&self, t: f32) -> Vector3<f32> {
+        self.origin + self.direction * t
+    }
+
+    /// Where the ray meets the plane through `point` facing `normal`, as
+    /// the `t` of the hit. `None` if it runs parallel to the plane or the
+    /// plane is behind it.
+    pub fn intersect_plane(&self, point: &Vector3<f32>, normal: &Vector3<f32>) -> Option<f32> {
+        let facing = self.direction.dot(normal);
+        if facing.abs() < f32::EPSILON {
+            return Option::None
+        }
+        let t = (point - self.origin).dot(normal) / facing;
+        if t < 0. {
+            return Option::None
+        }
+        Option::Some(t)
+    }
+
+    /// Where the ray enters the axis aligned box between the corners `min`
+    /// and `max`, as the `t` of the hit. A ray starting inside hits at 0.
+    pub fn intersect_aabb(&self, min: &Vector3<f32>, max: &Vector3<f32>) -> Option<f32> {
+        // Slab method: the ray is inside the box where it's between both
+        // planes of every axis at once
+        let (mut enter, mut exit) = (0., f32::INFINITY);
+        for axis in 0 .. 3 {
+            let (origin, direction) = (self.origin[axis], self.direction[axis]);
+            if direction.abs() < f32::EPSILON {
+                if origin < min[axis] || origin > max[axis] {
+                    return Option::None
+                }
+                continue
+            }
+            let near = (min[axis] - origin) / direction;
+            let far = (max[axis] - origin) / direction;
+            enter = near.min(far).max(enter);
+            exit = near.max(far).min(exit);
+            if enter > exit {
+                return Option::None
+            }
+        }
+        Option::Some(enter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ray(origin: (f32, f32, f32), direction: (f32, f32, f32)) -> Ray {
+        Ray::new(
+            Vector3::new(origin.0, origin.1, origin.2),
+            Vector3::new(direction.0, direction.1, direction.2)
+        )
+    }
+
+    const UP: (f32, f32, f32) = (0., 0., 1.);
+
+    #[test]
+    fn rays_hit_planes_in_front_of_them() {
+        let plane = Vector3::new(0., 0., 4.);
+        let normal = Vector3::new(0., 0., 1.);
+        let t = ray((1., 2., 0.), (0., 1., 2.)).intersect_plane(&plane, &normal);
+        assert_eq!(t, Option::Some(2.));
+        assert_eq!(ray((1., 2., 0.), (0., 1., 2.)).at(2.), Vector3::new(1., 4., 4.));
+
+        let away = ray((0., 0., 0.), (0., 0., -1.));
+        assert_eq!(away.intersect_plane(&plane, &normal), Option::None);
+    }
+
+    #[test]
+    fn parallel_rays_miss_planes() {
+        let plane = Vector3::new(0., 0., 4.);
+        let normal = Vector3::new(0., 0., 1.);
+        let below = ray((0., 0., 0.), (1., 1., 0.));
+        assert_eq!(below.intersect_plane(&plane, &normal), Option::None);
+        let within = ray((0., 0., 4.), (1., 0., 0.));
+        assert_eq!(within.intersect_plane(&plane, &normal), Option::None);
+    }
+
+    #[test]
+    fn rays_enter_boxes_at_the_nearest_face() {
+        let (min, max) = (Vector3::new(-1., -1., 2.), Vector3::new(1., 1., 4.));
+        assert_eq!(ray((0., 0., 0.), UP).intersect_aabb(&min, &max), Option::Some(2.));
+        let slow = ray((0.5, 0., 0.), (0., 0., 0.5));
+        assert_eq!(slow.intersect_aabb(&min, &max), Option::Some(4.));
+        // From the side
+        assert_eq!(ray((-3., 0., 3.), (1., 0., 0.)).intersect_aabb(&min, &max), Option::Some(2.));
+    }
+
+    #[test]
+    fn rays_miss_boxes_beside_or_behind_them() {
+        let (min, max) = (Vector3::new(-1., -1., 2.), Vector3::new(1., 1., 4.));
+        assert_eq!(ray((2., 0., 0.), UP).intersect_aabb(&min, &max), Option::None);
+        assert_eq!(ray((0., 0., 5.), UP).intersect_aabb(&min, &max), Option::None);
+        assert_eq!(ray((0., 0., 0.), (1., 0., 1.)).intersect_aabb(&min, &max), Option::None);
+    }
+
+    #[test]
+    fn parallel_rays_only_hit_boxes_they_run_inside() {
+        let (min, max) = (Vector3::new(-1., -1., 2.), Vector3::new(1., 1., 4.));
+        let through = ray((-5., 0., 3.), (1., 0., 0.));
+        assert_eq!(through.intersect_aabb(&min, &max), Option::Some(4.));
+        let above = ray((-5., 0., 5.), (1., 0., 0.));
+        assert_eq!(above.intersect_aabb(&min, &max), Option::None);
+    }
+
+    #[test]
+    fn rays_starting_inside_a_box_hit_at_once() {
+        let (min, max) = (Vector3::new(-1., -1., 2.), Vector3::new(1., 1., 4.));
+        assert_eq!(ray((0., 0., 3.), UP).intersect_aabb(&min, &max), Option::Some(0.));
+        let sideways = ray((0., 0., 3.), (1., -1., 0.));
+        assert_eq!(sideways.intersect_aabb(&min, &max), Option::Some(0.));
+    }
+}
@@ -1,12 +1,14 @@
 use nalgebra::{Vector4, Vector3, Matrix4};
-use glium::backend::{Facade};
 use crate::graphics::core::*;
 use crate::graphics::camera::Camera;
+use crate::graphics::light::{Light, LightBlock};
+use crate::graphics::ray::Ray;
 use std::path::Path;
 use glium::Surface;
 use glium::uniform;
-#[macro_use]
 
+/// Where a model is placed: its position and scale, and the model matrix
+/// built from them once something changed.
 pub struct ModelTrans {
     uniform_matrix: Matrix4<f32>,
     changed: bool,
@@ -31,6 +33,7 @@ impl Default for ModelTrans {
 
 impl ModelTrans {
 
+    /// The model matrix, as the shader takes it.
     pub fn as_array(&mut self) -> [[f32; 4]; 4] {
         if self.changed {
             self.update_matrix();
@@ -38,6 +41,7 @@ impl ModelTrans {
         *self.uniform_matrix.as_ref()
     }
 
+    /// The model matrix, as the shader takes it.
     pub fn as_ref(&mut self) -> &[[f32; 4]; 4] {
         if self.changed {
             self.update_matrix();
@@ -48,7 +52,7 @@ impl ModelTrans {
     fn update_matrix(&mut self) {
         let trans = nalgebra::geometry::Translation::from(self.position)
             .to_homogeneous();
-        let mut scale = nalgebra::Matrix4::from_diagonal(
+        let scale = nalgebra::Matrix4::from_diagonal(
             &nalgebra::Vector4::new(
                 self.scale[0],
                 self.scale[1],
@@ -56,63 +60,96 @@ impl ModelTrans {
                 1.0
             )
         );
-
-        let look_at = nalgebra::Rotation3::look_at_rh(&self.at, &self.up)
-            .to_homogeneous();
         self.uniform_matrix = trans * scale
     }
 
 
+    /// Moves the model to `(x, y, z)`.
     pub fn move_to(&mut self, x: f32, y: f32, z: f32) {
         self.position = nalgebra::Vector3::new(x,y,z);
         self.changed = true;
     }
 
+    /// Moves the model by `(x, y, z)`.
     pub fn add_position(&mut self, x: f32, y: f32, z: f32) {
         self.position += Vector3::new(x,y,z);
         self.changed = true;
     }
 
+    /// Moves the model by `dir`.
     pub fn add_position_vec(&mut self, dir: &Vector3<f32>) {
         self.position += dir;
         self.changed = true;
     }
 
+    /// The axis aligned box around the model space box from `min` to `max`
+    /// once moved into place.
+    pub fn transform_bounds(
+        &mut self,
+        min: &Vector3<f32>,
+        max: &Vector3<f32>
+    ) -> (Vector3<f32>, Vector3<f32>) {
+        if self.changed {
+            self.update_matrix();
+        };
+        let mut lo = Vector3::new(f32::INFINITY, f32::INFINITY, f32::INFINITY);
+        let mut hi = -lo;
+        for corner in 0 .. 8 {
+            let pick = |axis: usize| if corner & (1 << axis) == 0 { min[axis] } else { max[axis] };
+            let world = self.uniform_matrix * Vector4::new(pick(0), pick(1), pick(2), 1.);
+            for axis in 0 .. 3 {
+                lo[axis] = lo[axis].min(world[axis]);
+                hi[axis] = hi[axis].max(world[axis]);
+            }
+        }
+        (lo, hi)
+    }
+
+    /// Sets which way is up for the model. The matrix doesn't rotate models
+    /// yet.
     pub fn set_up(&mut self, up: nalgebra::Vector3<f32>) {
         self.up = up;
         self.changed = true
     }
 
+    /// Sets the direction the model faces. The matrix doesn't rotate models
+    /// yet.
     pub fn set_look_at(&mut self, at: nalgebra::Vector3<f32>) {
         self.at = at;
         self.changed = true
     }
 
+    /// Scales the model by `v` on every axis.
     pub fn set_uniform_scale(&mut self, v: f32) {
         self.scale = Vector3::new(v,v,v);
         self.changed = true
     }
 
+    /// Scales the model by `x`, `y` and `z` along those axes.
     pub fn set_scale(&mut self, x: f32, y:f32, z:f32) {
         self.scale = Vector3::new(x,y,z);
         self.changed = true
     }
 
+    /// Scales the model by `vec` along the axes.
     pub fn set_scale_vec(&mut self, vec: Vector3<f32>) {
         self.scale = vec;
         self.changed = true
     }
 
+    /// Multiplies the scale by `x`, `y` and `z` along those axes.
     pub fn scale_by(&mut self, x: f32, y:f32, z:f32) {
-        self.scale.component_mul(&Vector3::new(x,y,z));
+        self.scale = self.scale.component_mul(&Vector3::new(x,y,z));
         self.changed = true
     }
 
+    /// Multiplies the scale by `vec` along the axes.
     pub fn scale_by_vec(&mut self, vec: Vector3<f32>) {
-        self.scale.component_mul(&vec);
+        self.scale = self.scale.component_mul(&vec);
         self.changed = true
     }
 
+    /// Turns the facing direction around the axis across it.
     pub fn horizontal_rotate(&mut self, angle: f32) {
         let rot_vec = self.at.cross(&self.up);
         self.at = nalgebra::Rotation3::from_axis_angle(
@@ -121,6 +158,7 @@ impl ModelTrans {
         self.changed = true;
     }
 
+    /// Turns the facing direction around the y axis.
     pub fn vertical_rotate(&mut self, angle: f32) {
         let rot_vec = nalgebra::Vector3::new(0., 1., 0.);
         self.at = nalgebra::Rotation3::from_axis_angle(
@@ -132,41 +170,124 @@ impl ModelTrans {
 }
 
 
+/// Blend, depth and cull state to draw with. The default draws opaque
+/// geometry: nearest fragment wins and back faces are skipped.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DrawState {
+    /// How fragments are mixed with what's already drawn.
+    pub blend: glium::Blend,
+    /// Which fragments pass the depth test.
+    pub depth_test: glium::DepthTest,
+    /// Whether fragments that pass write their depth.
+    pub depth_write: bool,
+    /// Which faces are skipped.
+    pub cull: glium::BackfaceCullingMode,
+}
+
+impl Default for DrawState {
+    fn default() -> DrawState {
+        DrawState {
+            blend: Default::default(),
+            depth_test: glium::DepthTest::IfLess,
+            depth_write: true,
+            // Meshes wind their front faces counter clockwise
+            cull: glium::BackfaceCullingMode::CullClockwise,
+        }
+    }
+}
+
+impl DrawState {
+    /// Alpha blended and tested against the depth buffer without writing
+    /// to it, for see through objects drawn after the opaque ones.
+    pub fn translucent() -> DrawState {
+        DrawState {
+            blend: glium::Blend::alpha_blending(),
+            depth_write: false,
+            .. Default::default()
+        }
+    }
+
+    fn parameters(&self, viewport: glium::Rect) -> glium::DrawParameters<'static> {
+        glium::DrawParameters {
+            depth: glium::Depth {
+                test: self.depth_test,
+                write: self.depth_write,
+                .. Default::default()
+            },
+            blend: self.blend,
+            backface_culling: self.cull,
+            viewport: Option::Some(viewport),
+            .. Default::default()
+        }
+    }
+}
+
+
+/// A mesh to draw with its color, glyph and placement.
 pub struct RenderObject {
     base_rgba:  Vector4<f32>,
+    pattern:     i32,
     model_trans: ModelTrans,
     mesh_ref:    MeshRef,
+    // Overrides the renderer's draw state when set
+    draw_state:  Option<DrawState>,
 }
 
 impl RenderObject {
+    /// A red, opaque `mesh_ref` at the origin.
     pub fn new(mesh_ref: MeshRef) -> RenderObject {
         RenderObject {
             base_rgba: Vector4::new(1.,0.,0.,1.),
+            pattern: 0,
             model_trans: Default::default(),
-            mesh_ref: mesh_ref
+            mesh_ref,
+            draw_state: Option::None,
         }
     }
+    /// Sets the color, keeping the opacity.
     pub fn rgb(&mut self, r: f32, g: f32, b: f32) {
         self.base_rgba = Vector4::new(r,g,b,self.base_rgba[3])
     }
 
+    /// Sets the color from a vector, keeping the opacity.
     pub fn rgb_vec(&mut self, vec: Vector3<f32>) {
         self.base_rgba = Vector4::new(vec[0],vec[1],vec[2],self.base_rgba[3])
     }
 
 
+    /// Sets the opacity, 1 for opaque.
     pub fn opacity(&mut self, v: f32) {
        self.base_rgba[3] = v
     }
 
+    /// Sets the glyph the fragment shader draws on every face, 0 for none.
+    pub fn pattern(&mut self, pattern: i32) {
+        self.pattern = pattern
+    }
+
+    /// The placement of the object, to move and scale it.
     pub fn trans(&mut self) -> &mut ModelTrans {
         &mut self.model_trans
     }
+
+    fn instance(&mut self) -> Instance {
+        Instance {
+            model_mat: self.model_trans.as_array(),
+            rgba_color: *self.base_rgba.as_ref(),
+            pattern: self.pattern,
+        }
+    }
+
+    /// Draws this object with `state` instead of the renderer's, `None`
+    /// goes back to the renderer's.
+    pub fn draw_state(&mut self, state: Option<DrawState>) {
+        self.draw_state = state
+    }
 }
 
 
-// Dummy reference wrapper for mesh ids, wrapped so that we do not mistake it
-// for normal integers.
+/// Refers to a mesh loaded into a renderer, wrapped so it isn't mistaken
+/// for a plain integer.
 #[derive(Hash, Debug, PartialEq, Eq, Copy)]
 pub struct MeshRef {
     id : u64
@@ -178,13 +299,21 @@ impl Clone for MeshRef {
     }
 }
 
-struct MeshStore {
+/// The meshes a renderer loaded, found by reference or by name.
+pub struct MeshStore {
     mesh_ref: MeshRef,
     map: std::collections::HashMap<MeshRef, Mesh>,
     name_map: std::collections::HashMap<String, MeshRef>
 }
 
+impl Default for MeshStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MeshStore {
+    /// An empty store.
     pub fn new() -> MeshStore {
         MeshStore{
             mesh_ref: MeshRef{id:0},
@@ -197,101 +326,213 @@ impl MeshStore {
     fn insert(
         &mut self,
         name: &String,
-        make: &Fn() -> Result<Mesh, BufferCreationError>,
+        make: &dyn Fn() -> Result<Mesh, BufferCreationError>,
     ) -> Result<MeshRef, BufferCreationError>
     {
-        let content = self.name_map.get(name).map(|e| e.clone());
+        let content = self.name_map.get(name).copied();
         match content {
             Option::Some(reference) => {
-                return Result::Ok(reference);
+                Result::Ok(reference)
             },
             Option::None => {
                 let id = self.gen_id();
                 let mesh = make()?;
                 self.map.entry(id).or_insert(mesh);
-                return Result::Ok(id)
+                self.name_map.insert(name.clone(), id);
+                Result::Ok(id)
             }
         }
     }
 
+    /// The mesh `reference` points to.
     pub fn get_mesh(&self, reference: &MeshRef) -> Option<&Mesh> {
         self.map.get(reference)
     }
 
+    /// The reference to the mesh loaded as `name`.
     pub fn get_ref(&self, name: &String) -> Option<MeshRef> {
-        self.name_map.get(name).map(|e|{*e})
+        self.name_map.get(name).copied()
     }
 
     fn gen_id(&mut self) -> MeshRef {
         let result = self.mesh_ref;
         self.mesh_ref.id += 1;
-        return result
+        result
+    }
+}
+
+
+// One batch for every mesh and draw state, in the order of their first
+// object. Unbatched, every object gets a batch of its own.
+fn batch(
+    objects: &mut [RenderObject],
+    default: DrawState,
+    batched: bool
+) -> Vec<(MeshRef, DrawState, Vec<Instance>)> {
+    let mut batches: Vec<(MeshRef, DrawState, Vec<Instance>)> = Vec::new();
+    for obj in objects.iter_mut() {
+        let state = obj.draw_state.unwrap_or(default);
+        let instance = obj.instance();
+        let batch = batches.iter_mut()
+            .find(|(mesh_ref, s, _)| batched && *mesh_ref == obj.mesh_ref && *s == state);
+        match batch {
+            Option::Some((_, _, instances)) => instances.push(instance),
+            Option::None => batches.push((obj.mesh_ref, state, vec![instance]))
+        }
+    }
+    batches
+}
+
+// The size of the buffer to make for `needed` instances when the one there
+// holds `len`, or `None` to keep it. New buffers fit exactly, outgrown ones
+// are replaced by the next power of two so they aren't replaced every frame.
+fn grown_capacity(len: Option<usize>, needed: usize) -> Option<usize> {
+    match len {
+        Option::None => Option::Some(needed),
+        Option::Some(len) if len < needed => Option::Some(needed.next_power_of_two()),
+        Option::Some(_) => Option::None
     }
 }
 
 
+/// Draws `RenderObject`s on a display with one shader program, lit by a
+/// single `Light`.
 pub struct Renderer<'a> {
     display: &'a glium::Display,
     program: Option<glium::Program>,
     mesh_store: MeshStore,
+    draw_state: DrawState,
+    light: Light,
+    light_buffer: glium::uniforms::UniformBuffer<LightBlock>,
+    // One per batch, kept between frames and only replaced when too small
+    instance_buffers: Vec<glium::VertexBuffer<Instance>>,
 }
 
 
 impl<'a> Renderer<'a> {
 
+    /// A renderer for `display` with no program, see `use_program`.
     pub fn new(
         display: &'a glium::Display
-    ) -> Renderer<'a> {
-        Renderer{
-            display: display,
+    ) -> Result<Renderer<'a>, BufferCreationError> {
+        let light_buffer = glium::uniforms::UniformBuffer::new(
+            display,
+            LightBlock::from(&Light::default())
+        ).map_err(BufferCreationError::UniformError)?;
+        Result::Ok(Renderer{
+            display,
             program: Option::None,
             mesh_store: MeshStore::new(),
-        }
+            draw_state: Default::default(),
+            light: Default::default(),
+            light_buffer,
+            instance_buffers: Vec::new(),
+        })
     }
 
+    /// The meshes loaded so far.
     pub fn mesh_store(&self) -> &MeshStore {
         &self.mesh_store
     }
 
+    /// The light objects are lit by.
+    pub fn light(&self) -> &Light {
+        &self.light
+    }
+
+    /// Lights objects with `light` from the next frame on.
+    pub fn set_light(&mut self, light: Light) {
+        self.light = light;
+        self.light_buffer.write(&LightBlock::from(&light));
+    }
+
+    /// The state objects without one of their own are drawn with. Depth
+    /// testing needs a display created with a depth buffer.
+    pub fn set_draw_state(&mut self, state: DrawState) {
+        self.draw_state = state
+    }
+
+    /// Draws `objects` with one instanced draw call for every mesh and
+    /// draw state they use. Batches go in the order of their first object,
+    /// so translucent objects should come after all the opaque ones.
     pub fn render(
         &mut self,
-        objects :&mut Vec<RenderObject>,
+        objects :&mut [RenderObject],
+        camera: &mut Camera
+    ) {
+        self.draw(objects, camera, true)
+    }
+
+    /// Like `render` but with a draw call for every object, to compare
+    /// against.
+    pub fn render_unbatched(
+        &mut self,
+        objects :&mut [RenderObject],
         camera: &mut Camera
     ) {
+        self.draw(objects, camera, false)
+    }
+
+    fn draw(
+        &mut self,
+        objects: &mut [RenderObject],
+        camera: &mut Camera,
+        batched: bool
+    ) {
+        let batches = batch(objects, self.draw_state, batched);
+
         let mut target = self.display.draw();
-        target.clear_color(0.,0.,0.,0.);
-        let cam_mat = camera.as_primitive();
-        let program = self.program.as_mut().unwrap();
-
-        for obj in objects.into_iter() {
-            let mesh_ref = obj.mesh_ref.clone();
-            let model_mat = obj.model_trans.as_array();
-            let uniforms = uniform! {
-                camera_mat: cam_mat,
-                model_mat: model_mat,
-                rgba_color: *obj.base_rgba.as_ref()
-            };
+        target.clear_color_and_depth((0.,0.,0.,0.), 1.);
+        let eye = camera.eye();
+        let uniforms = uniform! {
+            camera_mat: camera.as_primitive(),
+            eye_position: [eye[0], eye[1], eye[2]],
+            Light: &self.light_buffer
+        };
+        let (width, height) = target.get_dimensions();
+        let (left, top, fitted_width, fitted_height) = camera.viewport((width as f64, height as f64));
+        let viewport = glium::Rect {
+            left: left as u32,
+            bottom: (height as f64 - top - fitted_height) as u32,
+            width: fitted_width as u32,
+            height: fitted_height as u32,
+        };
+        let program = self.program.as_ref().unwrap();
+
+        for (i, (mesh_ref, state, instances)) in batches.iter().enumerate() {
+            let len = self.instance_buffers.get(i).map(|buffer| buffer.len());
+            if let Option::Some(capacity) = grown_capacity(len, instances.len()) {
+                let buffer = glium::VertexBuffer::empty_dynamic(self.display, capacity).unwrap();
+                match len {
+                    Option::Some(_) => self.instance_buffers[i] = buffer,
+                    Option::None => self.instance_buffers.push(buffer)
+                }
+            }
+            let buffer = self.instance_buffers[i].slice(0 .. instances.len()).unwrap();
+            buffer.write(instances);
 
-            let mesh = self.mesh_store.get_mesh(&mesh_ref)
+            let mesh = self.mesh_store.get_mesh(mesh_ref)
                 .unwrap();
-
-            mesh.draw(
+            mesh.draw_instanced(
                 &mut target,
-                &program,
+                program,
+                buffer.per_instance().unwrap(),
                 &uniforms,
-                &Default::default()
+                &state.parameters(viewport)
             ).unwrap()
         };
 
-        target.finish();
+        target.finish().unwrap();
     }
 
+    /// Loads a mesh from `indices` and `vertices` as `name`, unless one is
+    /// already loaded by that name.
     pub fn load_mesh(
         &mut self,
         name: &String,
         indices: &[u16],
         vertices: &[Vertex]
-    ) -> Result<&Renderer, BufferCreationError> {
+    ) -> Result<&Renderer<'a>, BufferCreationError> {
         let f = self.display;
         self.mesh_store.insert(name, &|| {
             Mesh::new(f,indices, vertices)}
@@ -299,10 +540,12 @@ impl<'a> Renderer<'a> {
         Result::Ok(self)
     }
 
+    /// Loads the mesh `init_fn` makes as `name`, unless one is already loaded by
+    /// that name. Returns the reference to draw it with.
     pub fn load_mesh_with(
         &mut self,
         name: &String,
-        init_fn: &Fn(&glium::Display) -> Result<Mesh, BufferCreationError>
+        init_fn: &dyn Fn(&glium::Display) -> Result<Mesh, BufferCreationError>
     ) -> Result<MeshRef, BufferCreationError>
     {
         let f = self.display;
@@ -313,15 +556,37 @@ impl<'a> Renderer<'a> {
     }
 
 
+    /// The reference to the mesh loaded as `name`.
     pub fn get_mesh(&self, name: &String) -> Option<MeshRef> {
         self.mesh_store.get_ref(name)
     }
 
+    /// Index of the nearest object `ray` hits, going by the box around its
+    /// mesh moved into place.
+    pub fn pick(&self, ray: &Ray, objects: &mut [RenderObject]) -> Option<usize> {
+        let mut nearest: Option<(usize, f32)> = Option::None;
+        for (i, obj) in objects.iter_mut().enumerate() {
+            let (min, max) = match self.mesh_store.get_mesh(&obj.mesh_ref) {
+                Option::Some(mesh) => mesh.bounds(),
+                Option::None => continue
+            };
+            let (min, max) = obj.model_trans.transform_bounds(&min, &max);
+            if let Option::Some(t) = ray.intersect_aabb(&min, &max) {
+                if nearest.is_none_or(|(_, best)| t < best) {
+                    nearest = Option::Some((i, t));
+                }
+            }
+        }
+        nearest.map(|(i, _)| i)
+    }
+
+    /// Compiles the shaders in `vertex_fp` and `fragment_fp` and draws with
+    /// them from now on.
     pub fn use_program<P>(
         &mut self,
         vertex_fp: P,
         fragment_fp: P
-    ) -> Result<&Renderer, ProgramCreationError>
+    ) -> Result<&Renderer<'a>, ProgramCreationError>
     where P: AsRef<Path> {
         self.program = crate::graphics::core::simple_program(
             self.display,
@@ -331,3 +596,58 @@ impl<'a> Renderer<'a> {
         Result::Ok(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(id: u64, state: Option<DrawState>) -> RenderObject {
+        let mut obj = RenderObject::new(MeshRef{id});
+        obj.draw_state(state);
+        obj
+    }
+
+    fn keys(batches: &[(MeshRef, DrawState, Vec<Instance>)]) -> Vec<(u64, bool, usize)> {
+        batches.iter()
+            .map(|(mesh_ref, state, instances)| (mesh_ref.id, state.depth_write, instances.len()))
+            .collect()
+    }
+
+    #[test]
+    fn objects_are_batched_by_mesh_and_draw_state() {
+        let glass = Option::Some(DrawState::translucent());
+        let mut objects = vec![
+            object(1, Option::None),
+            object(2, Option::None),
+            object(1, glass),
+            object(1, Option::None),
+            object(1, Option::Some(DrawState::default())),
+            object(2, glass),
+        ];
+        objects[3].opacity(0.5);
+
+        let batches = batch(&mut objects, DrawState::default(), true);
+        assert_eq!(keys(&batches), vec![(1, true, 3), (2, true, 1), (1, false, 1), (2, false, 1)]);
+        let opacity: Vec<f32> = batches[0].2.iter().map(|i| i.rgba_color[3]).collect();
+        assert_eq!(opacity, vec![1., 0.5, 1.]);
+
+        let batches = batch(&mut objects, DrawState::translucent(), true);
+        assert_eq!(keys(&batches), vec![(1, false, 3), (2, false, 2), (1, true, 1)]);
+    }
+
+    #[test]
+    fn unbatched_objects_are_drawn_one_by_one() {
+        let mut objects = vec![object(1, Option::None), object(1, Option::None)];
+        let batches = batch(&mut objects, DrawState::default(), false);
+        assert_eq!(keys(&batches), vec![(1, true, 1), (1, true, 1)]);
+    }
+
+    #[test]
+    fn instance_buffers_only_grow_when_outgrown() {
+        assert_eq!(grown_capacity(Option::None, 5), Option::Some(5));
+        assert_eq!(grown_capacity(Option::Some(5), 5), Option::None);
+        assert_eq!(grown_capacity(Option::Some(8), 3), Option::None);
+        assert_eq!(grown_capacity(Option::Some(5), 6), Option::Some(8));
+        assert_eq!(grown_capacity(Option::Some(8), 9), Option::Some(16));
+    }
+}
@@ -1,3 +1,5 @@
+//! Meshes built into the renderer.
+
 use crate::graphics::core::{Vertex, Mesh, BufferCreationError};
 use glium::backend::{Facade};
 
@@ -13,43 +15,66 @@ const UNIT_TRIANGLE_INDICES : [u16;3] = [
 ];
 
 
-const UNIT_CUBE_VERTICES : [Vertex;8] = [
-    Vertex::new(-1., 1., 1.),
-    Vertex::new(1., 1., 1.),
-    Vertex::new(1., 1.,-1.),
-    Vertex::new(-1., 1.,-1.),
-    Vertex::new(-1.,-1., 1.),
-    Vertex::new(1.,-1., 1.),
-    Vertex::new(1.,-1.,-1.),
-    Vertex::new(-1.,-1.,-1.)
+// Four corners for every face so each can carry its own normal. Faces
+// wind counter clockwise seen from outside, the UVs run -1 to 1 over each
+const UNIT_CUBE_VERTICES : [Vertex;24] = [
+    // +x
+    Vertex::with_normal([1., -1., -1.], [1., 0., 0.], [-1., -1.]),
+    Vertex::with_normal([1., 1., -1.], [1., 0., 0.], [1., -1.]),
+    Vertex::with_normal([1., 1., 1.], [1., 0., 0.], [1., 1.]),
+    Vertex::with_normal([1., -1., 1.], [1., 0., 0.], [-1., 1.]),
+    // -x
+    Vertex::with_normal([-1., -1., -1.], [-1., 0., 0.], [-1., -1.]),
+    Vertex::with_normal([-1., -1., 1.], [-1., 0., 0.], [-1., 1.]),
+    Vertex::with_normal([-1., 1., 1.], [-1., 0., 0.], [1., 1.]),
+    Vertex::with_normal([-1., 1., -1.], [-1., 0., 0.], [1., -1.]),
+    // +y
+    Vertex::with_normal([-1., 1., -1.], [0., 1., 0.], [-1., -1.]),
+    Vertex::with_normal([-1., 1., 1.], [0., 1., 0.], [-1., 1.]),
+    Vertex::with_normal([1., 1., 1.], [0., 1., 0.], [1., 1.]),
+    Vertex::with_normal([1., 1., -1.], [0., 1., 0.], [1., -1.]),
+    // -y
+    Vertex::with_normal([-1., -1., -1.], [0., -1., 0.], [-1., -1.]),
+    Vertex::with_normal([1., -1., -1.], [0., -1., 0.], [1., -1.]),
+    Vertex::with_normal([1., -1., 1.], [0., -1., 0.], [1., 1.]),
+    Vertex::with_normal([-1., -1., 1.], [0., -1., 0.], [-1., 1.]),
+    // +z
+    Vertex::with_normal([-1., -1., 1.], [0., 0., 1.], [-1., -1.]),
+    Vertex::with_normal([1., -1., 1.], [0., 0., 1.], [1., -1.]),
+    Vertex::with_normal([1., 1., 1.], [0., 0., 1.], [1., 1.]),
+    Vertex::with_normal([-1., 1., 1.], [0., 0., 1.], [-1., 1.]),
+    // -z
+    Vertex::with_normal([-1., -1., -1.], [0., 0., -1.], [-1., -1.]),
+    Vertex::with_normal([-1., 1., -1.], [0., 0., -1.], [-1., 1.]),
+    Vertex::with_normal([1., 1., -1.], [0., 0., -1.], [1., 1.]),
+    Vertex::with_normal([1., -1., -1.], [0., 0., -1.], [1., -1.])
 ];
 
 const UNIT_CUBE_INDICES : [u16;36] =  [
-    7, 3, 2,
-    7, 2, 6,
-    6, 2, 1,
-    6, 1, 5,
-    5, 1, 0,
-    5, 0, 4,
-    4, 0, 3,
-    4, 3, 7,
-    3, 0, 1,
-    3, 1, 2,
-    7, 5, 4,
-    7, 6, 5
+    0, 1, 2,
+    0, 2, 3,
+    4, 5, 6,
+    4, 6, 7,
+    8, 9, 10,
+    8, 10, 11,
+    12, 13, 14,
+    12, 14, 15,
+    16, 17, 18,
+    16, 18, 19,
+    20, 21, 22,
+    20, 22, 23
 ];
 
 
-
-
-
-pub fn make_unit_cube<'a, F>(facade: &'a F) -> Result<Mesh, BufferCreationError>
+/// A cube from -1 to 1 on every axis.
+pub fn make_unit_cube<F>(facade: &F) -> Result<Mesh, BufferCreationError>
 where F: Facade {
     Mesh::new(facade, &UNIT_CUBE_INDICES, &UNIT_CUBE_VERTICES)
 }
 
 
-pub fn make_unit_triangle<'a, F>(facade: &'a F) -> Result<Mesh, BufferCreationError>
+/// A flat triangle pointing up, in the z = 0 plane.
+pub fn make_unit_triangle<F>(facade: &F) -> Result<Mesh, BufferCreationError>
 where F: Facade {
     Mesh::new(facade, &UNIT_TRIANGLE_INDICES, &UNIT_TRIANGLE_VERTICES)
 }
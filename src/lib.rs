@@ -1,2 +1,33 @@
-mod graphics;
+//! Tetris game core with optional renderers.
+//!
+//! The game itself has no graphics dependencies, everything a frontend needs
+//! is exported from the crate root. The OpenGL renderer in `graphics` is only
+//! built with the `render` feature.
+#![warn(missing_docs)]
+
+#[cfg(feature = "render")]
+pub mod graphics;
 mod tetris;
+
+pub use crate::tetris::manager::{TetrisManager, FRAME};
+pub use crate::tetris::gamefield::{Block, BlockKind, ClearGravity, GameField};
+pub use crate::tetris::piece::{Piece, PieceType, Rotation, PIECE_TYPES};
+pub use crate::tetris::pieceset::{KickTable, PieceDef, PieceSet, PieceSetError};
+pub use crate::tetris::rotation::{self, RotationSystem};
+pub use crate::tetris::color::Color;
+pub use crate::tetris::skin::{Skin, SkinError};
+pub use crate::tetris::queue::PieceQueue;
+pub use crate::tetris::config::{GameConfig, Mode};
+pub use crate::tetris::timing::Timing;
+pub use crate::tetris::event::{Event, TSpin};
+pub use crate::tetris::input::{Action, Das};
+pub use crate::tetris::modifier::Modifier;
+pub use crate::tetris::notation::{NotationError, Setup};
+pub use crate::tetris::fumen;
+pub use crate::tetris::editor::Editor;
+pub use crate::tetris::puzzle::{Goal, Puzzle, PuzzleError, PuzzleProgress, PuzzleRun, PuzzleState};
+pub use crate::tetris::solver::{PcSolver, Placement, SolverError};
+pub use crate::tetris::finesse::{FinesseStats, PieceFinesse};
+pub use crate::tetris::stats::{ClearHistogram, Stats, StatsReport};
+pub use crate::tetris::records::{Record, Records};
+pub use crate::tetris::replay::Replay;
@@ -1,108 +1,128 @@
-extern crate glium;
+#[cfg(feature = "render")]
+mod window;
+#[cfg(feature = "tui")]
+mod tui;
 
-use glium::Surface;
-use crate::graphics::shapes;
-use crate::tetris::manager::TetrisManager;
-
-mod graphics;
-mod tetris;
-
-fn main() {
-    let mut events_loop = glium::glutin::EventsLoop::new();
-    let window = glium::glutin::WindowBuilder::new();
-    let context = glium::glutin::ContextBuilder::new();
-    let display = glium::Display::new(window, context, &events_loop).unwrap();
-
-    let mut renderer = graphics::renderer::Renderer::new(&display);
-    renderer.use_program("shaders/vertex.vert", "shaders/fragment.frag")
-        .unwrap();
-
-    let cube_mesh = renderer.load_mesh_with(
-        &String::from("unit_cube"),
-        &shapes::make_unit_cube
-    ).unwrap();
-
-    // let triangle_mesh =renderer.load_mesh_with(
-    //     &String::from("unit_triangle"),
-    //     &|f| { shapes::make_unit_triangle(f) }
-    // ).unwrap();
-
-    let mut camera = graphics::camera::Camera::default();
-    camera.aspect(0.5);
-    camera.set_far(8.);
-    camera.set_near(1.);
-    camera.position(0.,0.,-2.);
+#[cfg(feature = "render")]
+fn run_default() {
+    window::run();
+}
 
-    let mut do_loop = true;
+#[cfg(all(feature = "tui", not(feature = "render")))]
+fn run_default() {
+    tui::app::run(tetris::GameConfig::default()).unwrap();
+}
 
+#[cfg(not(any(feature = "tui", feature = "render")))]
+fn run_default() {
+    eprintln!("Built without a frontend, enable the render or tui feature");
+}
 
-    let mut manager = TetrisManager::new(20, 30);
-    let mut i = 0;
-    let mut last_tick = std::time::Instant::now();
-    while do_loop {
-        events_loop.poll_events(|event|{
-            match event {
-                glium::glutin::Event::WindowEvent {event, ..} => {
-                    use glium::glutin::WindowEvent;
-                    use glium::glutin::VirtualKeyCode;
-                    use glium::glutin::ElementState;
-                    match event {
-                        WindowEvent::Resized(size) => {
-                            camera.aspect_of(size.into());
-                        },
-                        WindowEvent::KeyboardInput{input,..} => {
-                            if input.state == ElementState::Pressed {
-                                match input.virtual_keycode {
-                                    Option::Some(code) => {
-                                        match code {
-                                            VirtualKeyCode::W => {
-                                                manager.rotate_right();
-                                            },
-                                            VirtualKeyCode::A => {
-                                                manager.move_left();
-                                            },
-                                            VirtualKeyCode::D => {
-                                                manager.move_right();
-                                            },
-                                            VirtualKeyCode::S => {
-                                                manager.tick();
-                                            },
-                                            _ => ()
-                                        }
-                                    },
-                                    Option::None => ()
-                                }
-                            }
-                        },
-                        WindowEvent::CloseRequested => {
-                            println!("Got break request!");
-                            do_loop = false;
-                        },
-                        _ => ()
+fn main() {
+    #[cfg(feature = "tui")]
+    {
+        let args: Vec<String> = std::env::args().collect();
+        if let Option::Some(i) = args.iter().position(|arg| arg == "--puzzles") {
+            let dir = args.get(i + 1).map_or("puzzles", |dir| dir.as_str());
+            tui::app::run_puzzles(dir).unwrap();
+            return
+        }
+        if args.iter().any(|arg| arg == "--tui") {
+            let value_of = |flag: &str| {
+                args.iter().position(|arg| arg == flag)
+                    .map(|i| args.get(i + 1).map_or("", |value| value.as_str()))
+            };
+            let mut config = tetris::GameConfig::default();
+            if let Option::Some(name) = value_of("--mode") {
+                match tetris::Mode::from_name(name) {
+                    Option::Some(mode) => config = config.mode(mode),
+                    Option::None => {
+                        eprintln!("Unknown mode '{}', use sprint, marathon, ultra, master or practice", name);
+                        return
                     }
-                },
-                _ => ()
+                }
             }
-        });
-
-
-        if last_tick.elapsed().as_secs() > 1 {
-            manager.tick();
-            last_tick = std::time::Instant::now()
+            if let Option::Some(name) = value_of("--skin") {
+                // A built in skin or the path of a skin file
+                let skin = match tetris::Skin::preset(name) {
+                    Option::Some(skin) => Result::Ok(skin),
+                    Option::None => tetris::Skin::load(name),
+                };
+                match skin {
+                    Result::Ok(skin) => config = config.skin(skin),
+                    Result::Err(e) => {
+                        eprintln!("Couldn't load skin {}: {}", name, e);
+                        return
+                    }
+                }
+            }
+            if let Option::Some(name) = value_of("--pieces") {
+                // A built in set or the path of a set file
+                let set = match tetris::PieceSet::builtin(name) {
+                    Option::Some(set) => Result::Ok(set),
+                    Option::None => tetris::PieceSet::load(name),
+                };
+                match set {
+                    Result::Ok(set) => config = config.piece_set(set),
+                    Result::Err(e) => {
+                        eprintln!("Couldn't load piece set {}: {}", name, e);
+                        return
+                    }
+                }
+            }
+            if let Option::Some(name) = value_of("--rotation") {
+                match tetris::rotation::from_name(name) {
+                    Option::Some(system) => config = config.rotation_system(system),
+                    Option::None => {
+                        eprintln!("Unknown rotation system '{}', use srs, ars, nrs or sega", name);
+                        return
+                    }
+                }
+            }
+            if let Option::Some(frames) = value_of("--are") {
+                match frames.parse() {
+                    Result::Ok(frames) => config = config.are(frames),
+                    Result::Err(_) => {
+                        eprintln!("--are takes a number of frames, not '{}'", frames);
+                        return
+                    }
+                }
+            }
+            if let Option::Some(frames) = value_of("--line-clear") {
+                match frames.parse() {
+                    Result::Ok(frames) => config = config.line_clear(frames),
+                    Result::Err(_) => {
+                        eprintln!("--line-clear takes a number of frames, not '{}'", frames);
+                        return
+                    }
+                }
+            }
+            if let Option::Some(names) = value_of("--modifiers") {
+                match tetris::Modifier::parse_list(names) {
+                    Option::Some(modifiers) => for modifier in modifiers {
+                        config = config.modifier(modifier);
+                    },
+                    Option::None => {
+                        eprintln!(
+                            "Unknown modifiers '{}', use invisible, big, mirror, no-hold or no-ghost",
+                            names
+                        );
+                        return
+                    }
+                }
+            }
+            if let Option::Some(name) = value_of("--gravity") {
+                match tetris::ClearGravity::from_name(name) {
+                    Option::Some(gravity) => config = config.clear_gravity(gravity),
+                    Option::None => {
+                        eprintln!("Unknown gravity '{}', use naive, sticky or cascade", name);
+                        return
+                    }
+                }
+            }
+            tui::app::run(config).unwrap();
+            return
         }
-
-        let scale = 0.5 / manager.num_columns() as f32  - 0.0001;
-        let nodes = manager.elems();
-        let mut elems = nodes.iter().map(|((x,y), color)| {
-            let mut obj = graphics::renderer::RenderObject::new(cube_mesh);
-            let (r,g,b) = color.into();
-            obj.trans().move_to(*x, *y, 0.);
-            obj.trans().add_position(0.5, 0.5, 0.);
-            obj.trans().set_uniform_scale(scale);
-            obj.rgb(r,g,b);
-            obj
-        }).collect();
-
-        renderer.render(&mut elems, &mut camera);
     }
+    run_default();
 }
@@ -1,33 +1,77 @@
-#[derive(Copy, Clone, Debug)]
-pub enum Color {
-    Red = 0,
-    Green,
-    Blue,
-    White,
-    Magenta,
-    Yellow
+/// Block color as 8 bit RGB, converts into an `(r, g, b)` triple in the 0 to
+/// 1 range.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Color {
+    /// Red channel.
+    pub r: u8,
+    /// Green channel.
+    pub g: u8,
+    /// Blue channel.
+    pub b: u8,
 }
 
-const COLORS : [Color; 6] =
-    [Color::Red, Color::Green, Color::Blue,
-     Color::White, Color::Magenta, Color::Yellow];
+impl From<&Color> for (f32,f32,f32) {
+    fn from(val: &Color) -> Self {
+        (val.r as f32 / 255., val.g as f32 / 255., val.b as f32 / 255.)
+    }
+}
+
+impl Color {
+    /// `#ff0000`
+    pub const RED: Color = Color::rgb(255, 0, 0);
+    /// `#00ff00`
+    pub const GREEN: Color = Color::rgb(0, 255, 0);
+    /// `#0000ff`
+    pub const BLUE: Color = Color::rgb(0, 0, 255);
+    /// `#ffffff`
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+    /// `#ff00ff`
+    pub const MAGENTA: Color = Color::rgb(255, 0, 255);
+    /// `#ffff00`
+    pub const YELLOW: Color = Color::rgb(255, 255, 0);
+    /// `#808080`, the default garbage color.
+    pub const GRAY: Color = Color::rgb(128, 128, 128);
+    /// `#00ffff`
+    pub const CYAN: Color = Color::rgb(0, 255, 255);
+    /// `#ff8000`
+    pub const ORANGE: Color = Color::rgb(255, 128, 0);
+    /// `#a000f0`
+    pub const PURPLE: Color = Color::rgb(160, 0, 240);
+
+    /// A color from its channels.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Color {
+        Color { r, g, b }
+    }
 
-impl Into<(f32,f32,f32)> for &Color {
-    fn into(self) -> (f32,f32,f32) {
-        match self {
-            Color::Red => (1.,0.,0.),
-            Color::Green => (0.,1.,0.),
-            Color::Blue => (0.,0.,1.),
-            Color::White => (1.,1.,1.),
-            Color::Magenta => (1.,0.,1.),
-            Color::Yellow => (1.,1.,0.)
+    /// Parses `#rrggbb`, the `#` is optional.
+    pub fn from_hex(text: &str) -> Option<Color> {
+        let hex = text.trim().trim_start_matches('#');
+        // from_str_radix would also take a sign in front of the digits
+        if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Option::None
         }
+        let channel = |i: usize| u8::from_str_radix(&hex[i .. i + 2], 16).ok();
+        Option::Some(Color::rgb(channel(0)?, channel(2)?, channel(4)?))
+    }
+
+    /// The color as `#rrggbb`, the way `from_hex` reads it.
+    pub fn to_hex(self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
     }
 }
 
-impl Color {
-    pub fn from_rng<R: rand::Rng>(rng: &mut R) -> Color {
-        let n: usize = rng.gen();
-        COLORS[n % COLORS.len()]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_colors_need_six_digits() {
+        assert_eq!(Color::from_hex("#ff8000"), Option::Some(Color::ORANGE));
+        assert_eq!(Color::from_hex(" A000F0 "), Option::Some(Color::PURPLE));
+        let dark = Color::rgb(1, 2, 3);
+        assert_eq!(Color::from_hex(&dark.to_hex()), Option::Some(dark));
+        for bad in ["#+1+2+3", "#fff", "#ff80000", "#gg0000", "#ff80 0", "#ff80é"] {
+            assert_eq!(Color::from_hex(bad), Option::None, "{}", bad);
+        }
     }
 }
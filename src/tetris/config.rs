@@ -0,0 +1,263 @@
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::tetris::gamefield::ClearGravity;
+use crate::tetris::modifier::Modifier;
+use crate::tetris::pieceset::PieceSet;
+use crate::tetris::rotation::{RotationSystem, Srs};
+use crate::tetris::skin::Skin;
+
+/// What a game is played for. Results are only compared within a mode.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Clear 40 lines as fast as possible.
+    Sprint,
+    /// Play until topping out, for score.
+    Marathon,
+    /// Score as much as possible in two minutes.
+    Ultra,
+    /// Reach level 999 while gravity climbs to 20G and the delays shrink,
+    /// with the timing of TGM.
+    Master,
+    /// Play without a goal, placements can be undone and redone. Results
+    /// aren't kept.
+    Practice,
+}
+
+impl Mode {
+    /// The mode called `name`, as listed by `name`.
+    pub fn from_name(name: &str) -> Option<Mode> {
+        match name {
+            "sprint" => Option::Some(Mode::Sprint),
+            "marathon" => Option::Some(Mode::Marathon),
+            "ultra" => Option::Some(Mode::Ultra),
+            "master" => Option::Some(Mode::Master),
+            "practice" => Option::Some(Mode::Practice),
+            _ => Option::None
+        }
+    }
+
+    /// The name `from_name` takes, also used in file names.
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Sprint => "sprint",
+            Mode::Marathon => "marathon",
+            Mode::Ultra => "ultra",
+            Mode::Master => "master",
+            Mode::Practice => "practice",
+        }
+    }
+
+    /// Lines that end the game when cleared.
+    pub fn line_goal(self) -> Option<u32> {
+        match self {
+            Mode::Sprint => Option::Some(40),
+            _ => Option::None
+        }
+    }
+
+    /// Time after which the frontend ends the game.
+    pub fn time_limit(self) -> Option<Duration> {
+        match self {
+            Mode::Ultra => Option::Some(Duration::from_secs(120)),
+            _ => Option::None
+        }
+    }
+
+    /// Sprint results are times, master results levels and the others
+    /// scores.
+    pub fn lower_is_better(self) -> bool {
+        self == Mode::Sprint
+    }
+
+    /// A result of this mode for display, sprint times as minutes, seconds
+    /// and hundredths.
+    pub fn format_result(self, value: u64) -> String {
+        if self.lower_is_better() {
+            format!("{}:{:02}.{:02}", value / 60000, value / 1000 % 60, value % 1000 / 10)
+        } else {
+            value.to_string()
+        }
+    }
+}
+
+/// Settings used to start a new game, built by chaining the setters on
+/// `GameConfig::default()`.
+#[derive(Clone, Debug)]
+pub struct GameConfig {
+    /// Number of columns in the field.
+    pub width: i32,
+    /// Number of visible rows in the field.
+    pub height: i32,
+    /// Rows above the visible ones where pieces spawn.
+    pub buffer: i32,
+    /// Number of upcoming pieces shown.
+    pub preview: usize,
+    /// Seed of the piece queue, random when `None`.
+    pub seed: Option<u64>,
+    /// The mode to play.
+    pub mode: Mode,
+    /// The pieces dealt.
+    pub piece_set: PieceSet,
+    /// How the tetrominoes turn.
+    pub rotation_system: &'static dyn RotationSystem,
+    /// Entry delay (ARE) in frames between a lock and the next spawn, master
+    /// mode uses its own.
+    pub are: u32,
+    /// Frames full rows stay visible before they are removed, master mode
+    /// uses its own.
+    pub line_clear: u32,
+    /// How blocks fall after rows are cleared.
+    pub clear_gravity: ClearGravity,
+    /// Modifiers the game is played with, in any order.
+    pub modifiers: Vec<Modifier>,
+    /// Placements that can be undone in practice mode.
+    pub undo_depth: usize,
+    /// Colors of the pieces and garbage.
+    pub skin: Skin,
+}
+
+impl Default for GameConfig {
+    fn default() -> GameConfig {
+        GameConfig {
+            width: 10,
+            height: 20,
+            buffer: 20,
+            preview: 5,
+            seed: Option::None,
+            mode: Mode::Marathon,
+            piece_set: PieceSet::tetrominoes(),
+            rotation_system: &Srs,
+            are: 0,
+            line_clear: 0,
+            clear_gravity: ClearGravity::Naive,
+            modifiers: Vec::new(),
+            undo_depth: 100,
+            skin: Skin::guideline(),
+        }
+    }
+}
+
+impl GameConfig {
+    /// Number of columns in the field.
+    pub fn width(self, width: i32) -> GameConfig {
+        GameConfig { width, .. self }
+    }
+
+    /// Number of rows in the field.
+    pub fn height(self, height: i32) -> GameConfig {
+        GameConfig { height, .. self }
+    }
+
+    /// Number of hidden rows above the visible ones, pieces spawn there.
+    pub fn buffer(self, buffer: i32) -> GameConfig {
+        GameConfig { buffer, .. self }
+    }
+
+    /// Number of upcoming pieces visible in the queue.
+    pub fn preview(self, preview: usize) -> GameConfig {
+        GameConfig { preview, .. self }
+    }
+
+    /// Seed for the piece randomizer, the same seed always deals the same
+    /// pieces. Without a seed one is drawn from the OS.
+    pub fn seed(self, seed: u64) -> GameConfig {
+        GameConfig { seed: Option::Some(seed), .. self }
+    }
+
+    /// The mode to play, marathon by default.
+    pub fn mode(self, mode: Mode) -> GameConfig {
+        GameConfig { mode, .. self }
+    }
+
+    /// The pieces the queue deals.
+    pub fn piece_set(self, piece_set: PieceSet) -> GameConfig {
+        GameConfig { piece_set, .. self }
+    }
+
+    /// How the tetrominoes turn.
+    pub fn rotation_system(self, rotation_system: &'static dyn RotationSystem) -> GameConfig {
+        GameConfig { rotation_system, .. self }
+    }
+
+    /// Frames to wait after a lock before the next piece spawns. Rotation
+    /// and hold presses during the wait apply to the piece as it spawns.
+    pub fn are(self, are: u32) -> GameConfig {
+        GameConfig { are, .. self }
+    }
+
+    /// Frames full rows stay on the field before they are removed, for a
+    /// clear animation.
+    pub fn line_clear(self, line_clear: u32) -> GameConfig {
+        GameConfig { line_clear, .. self }
+    }
+
+    /// How blocks above cleared rows come down.
+    pub fn clear_gravity(self, clear_gravity: ClearGravity) -> GameConfig {
+        GameConfig { clear_gravity, .. self }
+    }
+
+    /// Adds a challenge modifier on top of any set before.
+    pub fn modifier(mut self, modifier: Modifier) -> GameConfig {
+        self.modifiers.push(modifier);
+        self
+    }
+
+    /// How many placements practice mode keeps to undo, the oldest are
+    /// dropped first.
+    pub fn undo_depth(self, undo_depth: usize) -> GameConfig {
+        GameConfig { undo_depth, .. self }
+    }
+
+    /// Colors for the pieces and garbage.
+    pub fn skin(self, skin: Skin) -> GameConfig {
+        GameConfig { skin, .. self }
+    }
+
+    /// Fingerprint of every setting that affects how a game plays, except
+    /// the seed, the skin and the undo depth. Stable between builds, so it
+    /// can be stored with results.
+    pub fn config_hash(&self) -> u64 {
+        // FNV-1a
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let fields = [
+            self.width as u64,
+            self.height as u64,
+            self.buffer as u64,
+            self.preview as u64,
+            self.mode as u64,
+        ];
+        // Settings added later only count when they differ from their
+        // default, so hashes stored before they existed still match
+        let mut later = String::new();
+        if self.piece_set.name != "tetrominoes" {
+            later.push_str(&format!("set={};", self.piece_set.name));
+        }
+        if self.rotation_system.name() != "srs" {
+            later.push_str(&format!("rotation={};", self.rotation_system.name()));
+        }
+        if self.are != 0 {
+            later.push_str(&format!("are={};", self.are));
+        }
+        if self.line_clear != 0 {
+            later.push_str(&format!("line_clear={};", self.line_clear));
+        }
+        if self.clear_gravity != ClearGravity::Naive {
+            later.push_str(&format!("gravity={};", self.clear_gravity.name()));
+        }
+        if !self.modifiers.is_empty() {
+            // The order they were added in doesn't matter
+            let mut names: Vec<String> = self.modifiers.iter().map(|m| m.to_string()).collect();
+            names.sort_unstable();
+            later.push_str(&format!("modifiers={};", names.join(",")));
+        }
+        let bytes = fields.iter()
+            .flat_map(|field| field.to_le_bytes().to_vec())
+            .chain(later.bytes());
+        for byte in bytes {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash
+    }
+}
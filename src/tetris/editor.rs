@@ -0,0 +1,184 @@
+use crate::tetris::config::GameConfig;
+use crate::tetris::fumen;
+use crate::tetris::gamefield::{Block, BlockKind, GameField};
+use crate::tetris::manager::TetrisManager;
+use crate::tetris::notation::{letter_color, NotationError, Setup};
+use crate::tetris::piece::{PieceType, PIECE_TYPES};
+
+/// Builds a `Setup` by hand: paints and erases cells with a brush, fills and
+/// clears rows and sets the queue and hold. Frontends map clicks to cells
+/// and keys to the other edits.
+pub struct Editor {
+    setup: Setup,
+    brush: BlockKind,
+}
+
+impl Editor {
+    /// An empty field of the given size, painting garbage.
+    pub fn new(width: i32, height: i32) -> Editor {
+        Editor::from_setup(Setup::new(GameField::new(width, height)))
+    }
+
+    /// Edits a copy of `setup`. Its active piece is dropped, games started
+    /// from the editor take the first piece from the queue.
+    pub fn from_setup(setup: Setup) -> Editor {
+        Editor {
+            setup: Setup { active: Option::None, .. setup },
+            brush: BlockKind::Garbage,
+        }
+    }
+
+    /// The setup as edited so far.
+    pub fn setup(&self) -> &Setup {
+        &self.setup
+    }
+
+    /// What painted cells are filled with.
+    pub fn brush(&self) -> BlockKind {
+        self.brush
+    }
+
+    /// Paints with `brush` from now on.
+    pub fn set_brush(&mut self, brush: BlockKind) {
+        self.brush = brush
+    }
+
+    /// Switches the brush to the next piece color, garbage after Z.
+    pub fn next_brush(&mut self) {
+        self.brush = match self.brush {
+            BlockKind::Garbage => BlockKind::Piece(PIECE_TYPES[0]),
+            BlockKind::Piece(piece_type) => {
+                match PIECE_TYPES.iter().position(|p| *p == piece_type) {
+                    Option::Some(i) if i + 1 < PIECE_TYPES.len() => {
+                        BlockKind::Piece(PIECE_TYPES[i + 1])
+                    },
+                    _ => BlockKind::Garbage
+                }
+            }
+        }
+    }
+
+    fn brush_block(&self) -> Block {
+        match self.brush {
+            BlockKind::Piece(piece_type) => Block {
+                kind: BlockKind::Piece(piece_type),
+                color: letter_color(piece_type),
+                locked: 0,
+            },
+            BlockKind::Garbage => Block::garbage(),
+        }
+    }
+
+    fn inside(&self, x: i32, y: i32) -> bool {
+        let field = &self.setup.field;
+        0 <= x && x < field.width() && 0 <= y && y < field.height()
+    }
+
+    /// Paints a cell with the brush, cells outside the field are ignored.
+    pub fn paint(&mut self, x: i32, y: i32) {
+        if self.inside(x, y) {
+            let block = self.brush_block();
+            self.setup.field.set_block(x, y, block);
+        }
+    }
+
+    /// Empties a cell, cells outside the field are ignored.
+    pub fn erase(&mut self, x: i32, y: i32) {
+        if self.inside(x, y) {
+            self.setup.field.clear_block(x, y);
+        }
+    }
+
+    /// Paints every empty cell of a row except the one in column `hole`, so
+    /// the row isn't full.
+    pub fn fill_row(&mut self, y: i32, hole: i32) {
+        for x in 0 .. self.setup.field.width() {
+            if x != hole && !self.setup.field.contains_node(x, y) {
+                self.paint(x, y);
+            }
+        }
+    }
+
+    /// Empties every cell of a row.
+    pub fn clear_row(&mut self, y: i32) {
+        for x in 0 .. self.setup.field.width() {
+            self.erase(x, y);
+        }
+    }
+
+    /// Adds a piece to the end of the queue.
+    pub fn push_queue(&mut self, piece_type: PieceType) {
+        self.setup.queue.push(piece_type);
+    }
+
+    /// Removes the last piece of the queue.
+    pub fn pop_queue(&mut self) -> Option<PieceType> {
+        self.setup.queue.pop()
+    }
+
+    /// Sets the held piece, `None` for an empty hold.
+    pub fn set_hold(&mut self, hold: Option<PieceType>) {
+        self.setup.hold = hold;
+    }
+
+    /// A game starting from the edited setup with the rules of `config`,
+    /// `None` while the queue is empty since the game would be out of pieces
+    /// right away.
+    pub fn start(&self, config: &GameConfig) -> Option<TetrisManager> {
+        if self.setup.queue.is_empty() {
+            return Option::None
+        }
+        Option::Some(TetrisManager::from_setup(&self.setup, config))
+    }
+
+    /// The setup in the text notation.
+    pub fn export(&self) -> String {
+        self.setup.to_string()
+    }
+
+    /// The setup as a fumen string, for the fields fumen supports.
+    pub fn export_fumen(&self) -> Result<String, NotationError> {
+        fumen::encode(&self.setup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn games_only_start_with_pieces_in_the_queue() {
+        let mut editor = Editor::new(10, 20);
+        assert!(editor.start(&GameConfig::default()).is_none());
+        editor.push_queue(PieceType::T);
+        editor.push_queue(PieceType::I);
+        let manager = editor.start(&GameConfig::default()).unwrap();
+        assert_eq!(manager.current().piece_type(), PieceType::T);
+        assert!(!manager.is_game_over());
+    }
+
+    #[test]
+    fn filled_rows_keep_their_hole() {
+        let mut editor = Editor::new(10, 20);
+        editor.next_brush();
+        editor.fill_row(19, 3);
+        editor.paint(10, 19);
+        editor.paint(0, -1);
+        let field = &editor.setup().field;
+        assert_eq!(field.get_blocks().len(), 9);
+        assert!(!field.contains_node(3, 19));
+        let painted = field.block_at(0, 19).map(|block| block.kind);
+        assert_eq!(painted, Option::Some(BlockKind::Piece(PIECE_TYPES[0])));
+        editor.clear_row(19);
+        assert!(editor.setup().field.is_empty());
+    }
+
+    #[test]
+    fn the_default_size_exports_to_fumen() {
+        let mut editor = Editor::new(10, 20);
+        editor.paint(0, 19);
+        editor.push_queue(PieceType::O);
+        let fumen = editor.export_fumen().unwrap();
+        assert_eq!(fumen::decode(&fumen, 20), Result::Ok(editor.setup().clone()));
+    }
+}
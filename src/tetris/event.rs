@@ -0,0 +1,49 @@
+use crate::tetris::input::Action;
+use crate::tetris::piece::PieceType;
+
+/// A T locked right after a rotation with three of its corners blocked.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TSpin {
+    /// Only one of the corners on the side the T points to is blocked.
+    Mini,
+    /// Both corners on the side the T points to are blocked.
+    Full,
+}
+
+/// Things that happened inside the game since the events were last drained
+/// with `TetrisManager::drain_events`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// The player performed an action, whether or not it had an effect.
+    Input(Action),
+    /// A new piece entered the field.
+    Spawned(PieceType),
+    /// The active piece was locked into the field.
+    Locked(PieceType),
+    /// One or more rows were cleared by the last lock.
+    LinesCleared(u32),
+    /// Rows filled by blocks falling after a clear, with the link of the
+    /// chain starting at 2. Followed by the `LinesCleared` of the link.
+    Chain(u32),
+    /// The rows of the last lock and every link after it are gone, with the
+    /// number of links. Comes a line clear delay after the lock, if any.
+    ChainEnded(u32),
+    /// The last lock was a T-spin, with the number of rows it cleared.
+    TSpin(TSpin, u32),
+    /// The last lock cleared every block off the field.
+    PerfectClear,
+    /// The locked piece took this many more inputs than needed.
+    FinesseFault(PieceType, u32),
+    /// The active piece was swapped into hold.
+    Held(PieceType),
+    /// A piece spawned or locked out of bounds, no more pieces can be played.
+    GameOver,
+    /// The goal of the game mode was reached, no more pieces can be played.
+    Finished,
+    /// A fixed queue ran out of pieces.
+    OutOfPieces,
+    /// The game went back to before the last placement.
+    Undone,
+    /// An undone placement was played again.
+    Redone,
+}
@@ -0,0 +1,193 @@
+use std::collections::{HashSet, VecDeque};
+use crate::tetris::gamefield::GameField;
+use crate::tetris::input::Action;
+use crate::tetris::piece::{Piece, PieceType};
+use crate::tetris::rotation;
+
+/// How one placement compares to the fewest inputs that reach it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PieceFinesse {
+    /// The piece that was placed.
+    pub piece_type: PieceType,
+    /// Moves, rotations and holds the player pressed. Drops aren't counted.
+    pub inputs: u32,
+    /// The fewest inputs that reach the same placement.
+    pub optimal: u32,
+}
+
+impl PieceFinesse {
+    /// Inputs pressed beyond the fewest.
+    pub fn faults(&self) -> u32 {
+        self.inputs.saturating_sub(self.optimal)
+    }
+}
+
+/// Finesse totals over a whole game.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FinesseStats {
+    /// Placements that were judged. Tucks and spins that can't be reached
+    /// by a hard drop from the spawn row are skipped.
+    pub pieces: u32,
+    /// Placements with at least one extra input.
+    pub faulty_pieces: u32,
+    /// Extra inputs over every judged placement.
+    pub faults: u32,
+    /// Inputs pressed for the judged placements.
+    pub inputs: u32,
+    /// The fewest inputs the judged placements needed.
+    pub optimal: u32,
+    /// The last judged placement.
+    pub last: Option<PieceFinesse>,
+}
+
+impl FinesseStats {
+    /// Adds a judged placement to the totals.
+    pub fn record(&mut self, piece: PieceFinesse) {
+        self.pieces += 1;
+        self.inputs += piece.inputs;
+        self.optimal += piece.optimal;
+        self.faults += piece.faults();
+        if piece.faults() > 0 {
+            self.faulty_pieces += 1;
+        }
+        self.last = Option::Some(piece);
+    }
+
+    /// Share of judged placements without faults, 1 when nothing was placed.
+    pub fn accuracy(&self) -> f32 {
+        if self.pieces == 0 {
+            return 1.
+        }
+        (self.pieces - self.faulty_pieces) as f32 / self.pieces as f32
+    }
+}
+
+/// Whether an action counts as a finesse input.
+pub fn is_input(action: Action) -> bool {
+    !matches!(action, Action::SoftDrop | Action::HardDrop)
+}
+
+fn drop_cells(field: &GameField, piece: Piece) -> Vec<(i32,i32)> {
+    let mut piece = piece;
+    while field.valid_piece(piece.move_down()) {
+        piece = piece.move_down();
+    }
+    let mut cells = piece.coordinates().to_vec();
+    cells.sort_unstable();
+    cells
+}
+
+fn shift(field: &GameField, piece: Piece, step: fn(&Piece) -> Piece) -> Piece {
+    let mut piece = piece;
+    while field.valid_piece(step(&piece)) {
+        piece = step(&piece);
+    }
+    piece
+}
+
+/// The fewest taps, wall shifts and rotations that take `spawn` somewhere a
+/// hard drop locks it on `target`. `None` if no such sequence exists, which
+/// means the placement needs a soft drop.
+pub fn optimal_inputs(
+    field: &GameField,
+    spawn: Piece,
+    target: &[(i32,i32)]
+) -> Option<u32> {
+    let mut target = target.to_vec();
+    target.sort_unstable();
+
+    let mut visited = HashSet::new();
+    let mut open = VecDeque::new();
+    visited.insert((spawn.rotation(), spawn.position()));
+    open.push_back((spawn, 0));
+    while let Option::Some((piece, cost)) = open.pop_front() {
+        if drop_cells(field, piece) == target {
+            return Option::Some(cost)
+        }
+        let moves = [
+            Option::Some(piece.move_left()),
+            Option::Some(piece.move_right()),
+            Option::Some(shift(field, piece, Piece::move_left)),
+            Option::Some(shift(field, piece, Piece::move_right)),
+            rotation::rotate(field, piece, piece.rotation().rotate_right()),
+            rotation::rotate(field, piece, piece.rotation().rotate_left()),
+            rotation::rotate(field, piece, piece.rotation().rotate_180()),
+        ];
+        for moved in moves.iter().flatten() {
+            if field.valid_piece(*moved)
+                && visited.insert((moved.rotation(), moved.position())) {
+                open.push_back((*moved, cost + 1));
+            }
+        }
+    }
+    Option::None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::config::GameConfig;
+    use crate::tetris::manager::TetrisManager;
+    use crate::tetris::notation::Setup;
+
+    fn game(queue: &str) -> TetrisManager {
+        let setup: Setup = format!("queue: {}\n{}", queue, "..........\n".repeat(20))
+            .parse()
+            .unwrap();
+        TetrisManager::from_setup(&setup, &GameConfig::default())
+    }
+
+    fn place(manager: &mut TetrisManager, actions: &[Action]) -> PieceFinesse {
+        for action in actions {
+            manager.apply(*action);
+        }
+        manager.apply(Action::HardDrop);
+        manager.finesse().last.unwrap()
+    }
+
+    #[test]
+    fn optimal_inputs_counts_taps_and_wall_shifts() {
+        let manager = game("OO");
+        let field = manager.field();
+        let spawn = manager.current();
+        let cells = |piece: Piece| drop_cells(field, piece);
+
+        assert_eq!(optimal_inputs(field, spawn, &cells(spawn)), Option::Some(0));
+        assert_eq!(optimal_inputs(field, spawn, &cells(spawn.move_left())), Option::Some(1));
+        let wall = shift(field, spawn, Piece::move_left);
+        assert_eq!(optimal_inputs(field, spawn, &cells(wall)), Option::Some(1));
+        let next_to_wall = wall.move_right();
+        assert_eq!(optimal_inputs(field, spawn, &cells(next_to_wall)), Option::Some(2));
+    }
+
+    #[test]
+    fn faults_count_the_extra_inputs() {
+        let mut manager = game("OO");
+        let piece = place(&mut manager, &[Action::MoveLeft, Action::MoveRight]);
+        assert_eq!((piece.inputs, piece.optimal, piece.faults()), (2, 0, 2));
+        assert_eq!(manager.finesse().faulty_pieces, 1);
+    }
+
+    #[test]
+    fn key_repeat_into_the_wall_is_one_input() {
+        let mut manager = game("OO");
+        let piece = place(&mut manager, &[Action::MoveLeft; 8]);
+        assert_eq!((piece.inputs, piece.optimal), (1, 1));
+        assert_eq!(manager.finesse().faults, 0);
+    }
+
+    #[test]
+    fn a_tap_then_das_into_the_wall_is_one_input() {
+        let mut manager = game("OO");
+        let piece = place(&mut manager, &[Action::MoveLeft, Action::DasLeft]);
+        assert_eq!((piece.inputs, piece.optimal, piece.faults()), (1, 1, 0));
+    }
+
+    #[test]
+    fn taps_short_of_the_wall_each_count() {
+        let mut manager = game("OO");
+        let piece = place(&mut manager, &[Action::MoveRight, Action::MoveLeft, Action::MoveLeft]);
+        assert_eq!((piece.inputs, piece.optimal), (3, 1));
+        assert_eq!(piece.faults(), 2);
+    }
+}
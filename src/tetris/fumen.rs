@@ -0,0 +1,506 @@
+//! Import and export of fumen (v115) strings, the board sharing format used
+//! by most community tools. Only the first page is read and a single page is
+//! written. Hold and queue are carried in a quiz comment `#Q=[hold](current)queue`.
+use crate::tetris::gamefield::{Block, BlockKind, GameField};
+use crate::tetris::notation::{letter_color, NotationError, Setup};
+use crate::tetris::piece::{Piece, PieceType};
+
+const PREFIX : &str = "v115@";
+const TABLE : &[u8] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const COMMENT_TABLE : &[u8] =
+    b" !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~";
+const COMMENT_BASE : u32 = 96;
+// The comment length is written with two characters
+const MAX_COMMENT : usize = 64 * 64 - 1;
+
+// Fumen fields are 10 wide with 23 rows plus one garbage row at the bottom
+const WIDTH : i32 = 10;
+const TOP : i32 = 23;
+const CELLS : usize = 240;
+
+// Block values, 0 is empty and 8 is garbage
+const PIECE_ORDER : [PieceType; 7] =
+    [PieceType::I, PieceType::L, PieceType::O, PieceType::Z,
+     PieceType::T, PieceType::J, PieceType::S];
+const GARBAGE : u32 = 8;
+
+// Rotation values, 0 Reverse, 1 Right, 2 Spawn, 3 Left
+const SEARCH_ORDER : [u32; 4] = [2, 1, 0, 3];
+
+fn fumen_error(why: &str) -> NotationError {
+    NotationError::UnsupportedFumen(why.to_string())
+}
+
+fn piece_value(piece_type: PieceType) -> u32 {
+    PIECE_ORDER.iter().position(|p| *p == piece_type).unwrap() as u32 + 1
+}
+
+fn block_value(block: &Block) -> u32 {
+    match block.kind {
+        BlockKind::Piece(piece_type) => piece_value(piece_type),
+        BlockKind::Garbage => GARBAGE,
+    }
+}
+
+// Cells of a piece relative to its fumen center, y pointing up
+fn fumen_shape(piece_type: PieceType, rotation: u32) -> [(i32,i32); 4] {
+    let spawn = match piece_type {
+        PieceType::I => [(0,0),(-1,0),(1,0),(2,0)],
+        PieceType::T => [(0,0),(-1,0),(1,0),(0,1)],
+        PieceType::O => [(0,0),(1,0),(0,1),(1,1)],
+        PieceType::L => [(0,0),(-1,0),(1,0),(1,1)],
+        PieceType::J => [(0,0),(-1,0),(1,0),(-1,1)],
+        PieceType::S => [(0,0),(-1,0),(0,1),(1,1)],
+        PieceType::Z => [(0,0),(1,0),(0,1),(-1,1)],
+        PieceType::Custom(_) => unreachable!("encode only takes tetrominoes"),
+    };
+    let mut res = spawn;
+    for (i, (x,y)) in spawn.iter().enumerate() {
+        res[i] = match rotation {
+            0 => (-x, -y),
+            1 => (*y, -x),
+            3 => (-y, *x),
+            _ => (*x, *y),
+        };
+    }
+    res
+}
+
+// Fumen stores some pieces relative to a different cell than their center
+fn position_shift(piece_type: PieceType, rotation: u32) -> (i32, i32) {
+    match (piece_type, rotation) {
+        (PieceType::O, 3) => (1, -1),
+        (PieceType::O, 0) => (1, 0),
+        (PieceType::O, 2) => (0, -1),
+        (PieceType::I, 0) => (1, 0),
+        (PieceType::I, 3) => (0, -1),
+        (PieceType::S, 2) => (0, -1),
+        (PieceType::S, 1) => (-1, 0),
+        (PieceType::Z, 2) => (0, -1),
+        (PieceType::Z, 3) => (1, 0),
+        _ => (0, 0),
+    }
+}
+
+struct Writer {
+    data: String,
+}
+
+impl Writer {
+    fn poke(&mut self, mut value: u32, chars: usize) {
+        for _ in 0 .. chars {
+            self.data.push(TABLE[(value % 64) as usize] as char);
+            value /= 64;
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: std::slice::Iter<'a, u8>,
+}
+
+impl<'a> Reader<'a> {
+    fn poll(&mut self, chars: usize) -> Result<u32, NotationError> {
+        let mut value = 0;
+        let mut scale = 1;
+        for _ in 0 .. chars {
+            let c = self.data.next().ok_or_else(|| fumen_error("data ends early"))?;
+            let n = TABLE.iter().position(|t| t == c)
+                .ok_or_else(|| fumen_error("invalid character"))?;
+            value += n as u32 * scale;
+            scale *= 64;
+        }
+        Result::Ok(value)
+    }
+}
+
+// Same escaping as javascript's escape(), which fumen applies to comments
+fn escape(text: &str) -> String {
+    let mut res = String::new();
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() || "@*_+-./".contains(c) {
+            res.push(c);
+        } else if (c as u32) < 256 {
+            res.push_str(&format!("%{:02X}", c as u32));
+        }
+    }
+    res
+}
+
+fn unescape(text: &str) -> String {
+    let mut res = String::new();
+    let mut chars = text.chars();
+    while let Option::Some(c) = chars.next() {
+        if c != '%' {
+            res.push(c);
+            continue
+        }
+        let hex: String = chars.by_ref().take(2).collect();
+        match u8::from_str_radix(&hex, 16) {
+            Result::Ok(n) => res.push(n as char),
+            Result::Err(_) => res.push_str(&hex),
+        }
+    }
+    res
+}
+
+fn quiz_comment(setup: &Setup) -> Option<String> {
+    if setup.hold.is_none() && setup.queue.is_empty() {
+        return Option::None
+    }
+    let mut queue = setup.queue.iter().map(|p| p.letter());
+    let current = match setup.active {
+        Option::Some(piece) => Option::Some(piece.piece_type().letter()),
+        Option::None => queue.next(),
+    };
+    Option::Some(format!(
+        "#Q=[{}]({}){}",
+        setup.hold.map(|p| p.letter().to_string()).unwrap_or_default(),
+        current.map(|p| p.to_string()).unwrap_or_default(),
+        queue.collect::<String>()
+    ))
+}
+
+// Returns hold, current and queue from a quiz comment
+fn parse_quiz(
+    comment: &str
+) -> Option<(Option<PieceType>, Option<PieceType>, Vec<PieceType>)> {
+    if !comment.starts_with("#Q=[") {
+        return Option::None
+    }
+    let rest = &comment[4 ..];
+    let hold_end = rest.find(']')?;
+    let hold = rest[.. hold_end].chars().next().and_then(PieceType::from_letter);
+    let rest = rest[hold_end + 1 ..].trim_start_matches('(');
+    let current_end = rest.find(')')?;
+    let current = rest[.. current_end].chars().next()
+        .and_then(PieceType::from_letter);
+    let queue = rest[current_end + 1 ..].chars()
+        .filter_map(PieceType::from_letter)
+        .collect();
+    Option::Some((hold, current, queue))
+}
+
+/// Writes a setup as a single page fumen string. The field has to be 10 wide
+/// and at most 23 rows tall, it is placed at the bottom of the fumen field.
+/// Blocks in buffer rows and a hold and queue too long for a comment are an
+/// error.
+pub fn encode(setup: &Setup) -> Result<String, NotationError> {
+    let height = setup.field.height();
+    if setup.field.width() != WIDTH || height > TOP {
+        return Result::Err(NotationError::FieldTooLarge)
+    }
+    let buffered = (-setup.field.buffer() .. 0)
+        .any(|y| (0 .. WIDTH).any(|x| setup.field.block_at(x, y).is_some()));
+    if buffered {
+        return Result::Err(fumen_error("blocks in buffer rows can't be encoded"))
+    }
+    let field_pieces = (0 .. height)
+        .flat_map(|y| (0 .. WIDTH).map(move |x| (x, y)))
+        .filter_map(|(x,y)| match setup.field.block_at(x, y)?.kind {
+            BlockKind::Piece(piece_type) => Option::Some(piece_type),
+            BlockKind::Garbage => Option::None,
+        });
+    let standard = field_pieces
+        .chain(setup.active.iter().map(|p| p.piece_type()))
+        .chain(setup.hold.iter().cloned())
+        .chain(setup.queue.iter().cloned())
+        .all(PieceType::is_tetromino);
+    if !standard {
+        return Result::Err(fumen_error("only the seven tetrominoes can be encoded"))
+    }
+
+    let mut cells = [0u32; CELLS];
+    for y in 0 .. height {
+        for x in 0 .. WIDTH {
+            if let Option::Some(block) = setup.field.block_at(x, y) {
+                cells[((TOP - height + y) * WIDTH + x) as usize] = block_value(&block);
+            }
+        }
+    }
+
+    let mut writer = Writer { data: String::new() };
+    let mut run_value = cells[0] + 8;
+    let mut run_length = 0;
+    for cell in cells[1 ..].iter() {
+        if cell + 8 == run_value {
+            run_length += 1;
+        } else {
+            writer.poke(run_value * CELLS as u32 + run_length, 2);
+            run_value = cell + 8;
+            run_length = 0;
+        }
+    }
+    writer.poke(run_value * CELLS as u32 + run_length, 2);
+    // An untouched field is followed by how many pages repeat it
+    if run_value == 8 && run_length == CELLS as u32 - 1 {
+        writer.poke(0, 1);
+    }
+
+    let (piece, rotation, position) = match setup.active {
+        Option::Some(active) => encode_piece(&active, height)?,
+        Option::None => (0, 0, 0),
+    };
+    let comment = quiz_comment(setup).map(|c| escape(&c));
+    if comment.as_ref().is_some_and(|c| c.len() > MAX_COMMENT) {
+        return Result::Err(fumen_error("the queue is too long for a comment"))
+    }
+    // Page flags, most significant first: unlocked, comment, colorize,
+    // mirror and rise
+    let mut flags = 0;
+    flags = flags * 2 + comment.is_some() as u32;
+    flags = flags * 2 + 1;
+    flags = flags * 2 * 2;
+    let action = ((flags * CELLS as u32 + position) * 4 + rotation) * 8 + piece;
+    writer.poke(action, 3);
+
+    if let Option::Some(comment) = comment {
+        let bytes = comment.as_bytes();
+        writer.poke(bytes.len() as u32, 2);
+        for chunk in bytes.chunks(4) {
+            let mut value = 0;
+            for (i, c) in chunk.iter().enumerate() {
+                let n = COMMENT_TABLE.iter().position(|t| t == c).unwrap() as u32;
+                value += n * COMMENT_BASE.pow(i as u32);
+            }
+            writer.poke(value, 5);
+        }
+    }
+
+    let data = writer.data;
+    let mut res = String::from(PREFIX);
+    res.push_str(&data[.. data.len().min(42)]);
+    for chunk in data.as_bytes()[data.len().min(42) ..].chunks(47) {
+        res.push('?');
+        res.push_str(std::str::from_utf8(chunk).unwrap());
+    }
+    Result::Ok(res)
+}
+
+// Returns the piece, rotation and position values of the active piece
+fn encode_piece(
+    piece: &Piece,
+    height: i32
+) -> Result<(u32, u32, u32), NotationError> {
+    let mut cells = piece.coordinates();
+    cells.sort_unstable();
+    for rotation in SEARCH_ORDER.iter() {
+        let mut shape: Vec<(i32,i32)> = fumen_shape(piece.piece_type(), *rotation)
+            .iter().map(|(x,y)| (*x, -y)).collect();
+        shape.sort_unstable();
+        let (dx, dy) = (cells[0].0 - shape[0].0, cells[0].1 - shape[0].1);
+        let fits = shape.iter().zip(cells.iter())
+            .all(|((sx,sy),(cx,cy))| sx + dx == *cx && sy + dy == *cy);
+        if fits {
+            let (shift_x, shift_y) = position_shift(piece.piece_type(), *rotation);
+            let x = dx - shift_x;
+            let y = height - 1 - dy - shift_y;
+            let position = (TOP - y - 1) * WIDTH + x;
+            if !(0..WIDTH).contains(&x) || position < 0 || position >= CELLS as i32 {
+                return Result::Err(NotationError::FieldTooLarge)
+            }
+            return Result::Ok((piece_value(piece.piece_type()), *rotation, position as u32))
+        }
+    }
+    Result::Err(NotationError::InvalidActive)
+}
+
+/// Reads the first page of a fumen string into a setup `height` rows tall.
+/// Blocks in the rows above `height` or in the garbage row are an error.
+pub fn decode(text: &str, height: i32) -> Result<Setup, NotationError> {
+    if height <= 0 {
+        return Result::Err(NotationError::EmptyField)
+    }
+    let start = text.find(PREFIX)
+        .ok_or_else(|| fumen_error("only v115 data is supported"))?;
+    let data: Vec<u8> = text[start + PREFIX.len() ..].bytes()
+        .filter(|c| *c != b'?')
+        .collect();
+    let mut reader = Reader { data: data.iter() };
+
+    let mut cells = [0u32; CELLS];
+    let mut i = 0;
+    while i < CELLS {
+        let run = reader.poll(2)?;
+        let value = run / CELLS as u32;
+        let length = (run % CELLS as u32) as usize + 1;
+        if !(8..=16).contains(&value) || i + length > CELLS {
+            return Result::Err(fumen_error("invalid field"))
+        }
+        for cell in cells[i .. i + length].iter_mut() {
+            *cell = value - 8;
+        }
+        if value == 8 && length == CELLS {
+            reader.poll(1)?;
+        }
+        i += length;
+    }
+
+    let mut field = GameField::new(WIDTH, height);
+    for (i, cell) in cells.iter().enumerate().filter(|(_, c)| **c != 0) {
+        let x = i as i32 % WIDTH;
+        let y = i as i32 / WIDTH - (TOP - height);
+        if y < 0 || y >= height {
+            return Result::Err(NotationError::FieldTooLarge)
+        }
+        let block = match *cell {
+            GARBAGE => Block::garbage(),
+            n => {
+                let piece_type = PIECE_ORDER[n as usize - 1];
+                Block {
+                    kind: BlockKind::Piece(piece_type),
+                    color: letter_color(piece_type),
+                    locked: 0,
+                }
+            }
+        };
+        field.set_block(x, y, block);
+    }
+
+    let mut action = reader.poll(3)?;
+    let piece = action % 8;
+    action /= 8;
+    let rotation = action % 4;
+    action /= 4;
+    let position = action % CELLS as u32;
+    action /= CELLS as u32;
+    let has_comment = (action >> 3) & 1 == 1;
+
+    let active = if piece == 0 || piece == GARBAGE {
+        Option::None
+    } else {
+        let piece_type = PIECE_ORDER[piece as usize - 1];
+        let (shift_x, shift_y) = position_shift(piece_type, rotation);
+        let x = (position as i32 % WIDTH) + shift_x;
+        let y = TOP - (position as i32 / WIDTH) - 1 + shift_y;
+        let cells: Vec<(i32,i32)> = fumen_shape(piece_type, rotation).iter()
+            .map(|(dx,dy)| (x + dx, height - 1 - (y + dy)))
+            .collect();
+        Option::Some(
+            Piece::from_coordinates(piece_type, letter_color(piece_type), &cells)
+                .ok_or(NotationError::InvalidActive)?
+        )
+    };
+
+    let mut setup = Setup::new(field);
+    setup.active = active;
+    if has_comment {
+        let length = reader.poll(2)? as usize;
+        let mut comment = String::new();
+        while comment.len() < length {
+            let mut value = reader.poll(5)?;
+            for _ in 0 .. 4 {
+                if comment.len() < length {
+                    let n = (value % COMMENT_BASE) as usize;
+                    let c = COMMENT_TABLE.get(n)
+                        .ok_or_else(|| fumen_error("invalid comment"))?;
+                    comment.push(*c as char);
+                    value /= COMMENT_BASE;
+                }
+            }
+        }
+        if let Option::Some((hold, current, queue)) = parse_quiz(&unescape(&comment)) {
+            setup.hold = hold;
+            setup.queue = queue;
+            match current {
+                Option::Some(current) if setup.active.is_none() => {
+                    setup.queue.insert(0, current)
+                },
+                _ => ()
+            }
+        }
+    }
+    Result::Ok(setup)
+}
+
+impl GameField {
+    /// Writes the field as a fumen string, see `fumen::encode`.
+    pub fn to_fumen(&self) -> Result<String, NotationError> {
+        encode(&Setup::new(self.clone()))
+    }
+
+    /// Reads a field from a fumen string, see `fumen::decode`.
+    pub fn from_fumen(text: &str, height: i32) -> Result<GameField, NotationError> {
+        decode(text, height).map(|setup| setup.field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::pieceset::PieceSet;
+
+    fn setup(text: &str) -> Setup {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn an_empty_field_is_the_usual_empty_fumen() {
+        let field = GameField::new(10, 20);
+        assert_eq!(field.to_fumen(), Result::Ok(String::from("v115@vhAAgH")));
+        assert_eq!(GameField::from_fumen("v115@vhAAgH", 20), Result::Ok(field));
+    }
+
+    #[test]
+    fn setups_survive_a_round_trip() {
+        let original = setup(
+            "hold: T\nqueue: IOSZJ\n\
+             ..........\n....s.....\n....ss....\n.....s....\n\
+             L.........\nL......ZZ#\nLL####.ZZ#"
+        );
+        let text = encode(&original).unwrap();
+        assert!(text.starts_with(PREFIX));
+        let decoded = decode(&text, 7).unwrap();
+        assert_eq!(decoded, original);
+    }
+
+    #[test]
+    fn the_current_piece_goes_back_to_the_queue_without_an_active_piece() {
+        let original = setup("hold: I\nqueue: TLJ\n..........\n##########");
+        let decoded = decode(&encode(&original).unwrap(), 2).unwrap();
+        assert_eq!(decoded, original);
+    }
+
+    #[test]
+    fn fields_fumen_cant_hold_are_rejected() {
+        let narrow = GameField::new(8, 20);
+        assert_eq!(narrow.to_fumen(), Result::Err(NotationError::FieldTooLarge));
+        let tall = GameField::new(10, 24);
+        assert_eq!(tall.to_fumen(), Result::Err(NotationError::FieldTooLarge));
+
+        let mut custom = Setup::new(GameField::new(10, 4));
+        custom.queue = PieceSet::pentominoes().pieces().to_vec();
+        assert!(matches!(encode(&custom), Result::Err(NotationError::UnsupportedFumen(_))));
+    }
+
+    #[test]
+    fn bad_fumen_data_is_rejected() {
+        let unsupported = |text| {
+            matches!(decode(text, 20), Result::Err(NotationError::UnsupportedFumen(_)))
+        };
+        assert!(unsupported("v110@vhAAgH"));
+        assert!(unsupported("v115@vh"));
+        let text = setup("#.........\n..........\n..........").field.to_fumen().unwrap();
+        assert_eq!(GameField::from_fumen(&text, 2), Result::Err(NotationError::FieldTooLarge));
+        for height in [0, -3] {
+            assert_eq!(decode("v115@vhAAgH", height), Result::Err(NotationError::EmptyField));
+        }
+    }
+
+    #[test]
+    fn what_fumen_cant_carry_is_rejected() {
+        let unsupported = |setup: &Setup| {
+            matches!(encode(setup), Result::Err(NotationError::UnsupportedFumen(_)))
+        };
+        let buffered = setup("buffer: 1\n#.........\n..........\n..........");
+        assert!(unsupported(&buffered));
+        assert!(encode(&setup("buffer: 1\n..........\n..........\n#.........")).is_ok());
+
+        let mut long = setup("..........");
+        long.queue = vec![PieceType::T; MAX_COMMENT];
+        assert!(unsupported(&long));
+        long.queue.truncate(100);
+        assert!(encode(&long).is_ok());
+    }
+}
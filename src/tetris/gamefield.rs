@@ -1,83 +1,236 @@
 use crate::tetris::color::Color;
-use crate::tetris::piece::Piece;
+use crate::tetris::piece::{Piece, PieceType, PIECE_TYPES};
+use crate::tetris::skin::Skin;
 
+/// What a locked block came from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlockKind {
+    /// Part of a locked piece.
+    Piece(PieceType),
+    /// Garbage, or a block painted in without a piece.
+    Garbage,
+}
+
+impl BlockKind {
+    /// Which glyph to draw over the block so pieces can be told apart
+    /// without color: 1 to 7 for the pieces I to Z, 0 for none. Pieces from
+    /// other sets get no glyph either.
+    pub fn glyph(self) -> u32 {
+        match self {
+            BlockKind::Piece(piece_type) => PIECE_TYPES.iter()
+                .position(|p| *p == piece_type)
+                .map_or(0, |i| i as u32 + 1),
+            BlockKind::Garbage => 0,
+        }
+    }
+}
+
+/// A block on the field.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Block {
+    /// What the block came from.
+    pub kind: BlockKind,
+    /// The color it's drawn in.
+    pub color: Color,
+    /// Frame of the game the block locked on, 0 for blocks that were there
+    /// from the start.
+    pub locked: u64,
+}
+
+impl Block {
+    /// A block of `piece`, in its color.
+    pub fn of_piece(piece: &Piece) -> Block {
+        Block {
+            kind: BlockKind::Piece(piece.piece_type()),
+            color: piece.color(),
+            locked: 0,
+        }
+    }
+
+    /// A gray garbage block.
+    pub fn garbage() -> Block {
+        Block {
+            kind: BlockKind::Garbage,
+            color: Color::GRAY,
+            locked: 0,
+        }
+    }
+
+    /// The block with its color taken from `skin`.
+    pub fn recolored(self, skin: &Skin) -> Block {
+        let color = match self.kind {
+            BlockKind::Piece(piece_type) => skin.color_of(piece_type),
+            BlockKind::Garbage => skin.garbage(),
+        };
+        Block { color, .. self }
+    }
+}
+
+/// How the blocks above cleared rows come down.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Default)]
+pub enum ClearGravity {
+    /// Everything above a cleared row moves down by one row, even blocks
+    /// left hanging over a hole.
+    #[default]
+    Naive,
+    /// Groups of touching blocks fall as units until they land.
+    Sticky,
+    /// Every block falls on its own until it lands, like in Puyo Puyo.
+    Cascade,
+}
+
+
+impl ClearGravity {
+    /// The way of falling called `name`, as listed by `name`.
+    pub fn from_name(name: &str) -> Option<ClearGravity> {
+        match name {
+            "naive" => Option::Some(ClearGravity::Naive),
+            "sticky" => Option::Some(ClearGravity::Sticky),
+            "cascade" => Option::Some(ClearGravity::Cascade),
+            _ => Option::None
+        }
+    }
+
+    /// The name `from_name` takes.
+    pub fn name(self) -> &'static str {
+        match self {
+            ClearGravity::Naive => "naive",
+            ClearGravity::Sticky => "sticky",
+            ClearGravity::Cascade => "cascade",
+        }
+    }
+}
+
+/// The grid of locked blocks. `(0,0)` is the top left visible cell and y
+/// grows downwards.
+///
+/// Above the visible rows is a buffer zone of `buffer` rows with negative y,
+/// where pieces spawn and blocks can be locked without being shown.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct GameField {
     width: i32,
     height: i32,
-    game_field: Vec<Option<Color>>,
+    buffer: i32,
+    game_field: Vec<Option<Block>>,
 }
 
 impl GameField {
 
+    /// A field without buffer rows.
     pub fn new(width: i32, height: i32) -> GameField {
+        GameField::with_buffer(width, height, 0)
+    }
+
+    /// A field with `buffer` rows above the visible ones.
+    pub fn with_buffer(width: i32, height: i32, buffer: i32) -> GameField {
+        let rows = (height + buffer) as usize;
         GameField {
-            width: width,
-            height: height,
-            game_field: vec![Option::None; width as usize * height as usize],
+            width,
+            height,
+            buffer,
+            game_field: vec![Option::None; width as usize * rows],
         }
     }
 
+    /// A copy of the field with `buffer` buffer rows. Blocks in buffer rows
+    /// that don't exist in the copy are dropped.
+    pub fn resized_buffer(&self, buffer: i32) -> GameField {
+        let mut res = GameField::with_buffer(self.width, self.height, buffer);
+        for y in -buffer.min(self.buffer) .. self.height {
+            for x in 0 .. self.width {
+                if let Option::Some(block) = self.block_at(x, y) {
+                    res.set_block(x, y, block);
+                }
+            }
+        }
+        res
+    }
+
     fn valid_index(&self, x: i32, y: i32) -> bool {
-        (0 <= x && x < self.width) && (0 <= y && y < self.height)
+        (0 <= x && x < self.width) && (-self.buffer <= y && y < self.height)
     }
 
     fn index_of(&self, x: i32, y: i32) -> usize {
-        (y * self.width + x) as usize
+        ((y + self.buffer) * self.width + x) as usize
     }
 
-    pub fn set_block(&mut self, x: i32, y: i32, color: Color) {
+    /// Puts `block` at `(x, y)`, which has to be on the field.
+    pub fn set_block(&mut self, x: i32, y: i32, block: Block) {
         let i = self.index_of(x,y);
-        self.game_field[i] = Option::Some(color)
+        self.game_field[i] = Option::Some(block)
     }
 
+    /// Empties `(x, y)`, which has to be on the field.
     pub fn clear_block(&mut self, x: i32, y: i32) {
         let i = self.index_of(x,y);
         self.game_field[i] = Option::None;
     }
 
 
-    fn from_index(&self, i: i32) -> (i32,i32) {
+    fn coordinates_of(&self, i: i32) -> (i32,i32) {
         use std::ops::Div;
-        let y = i.div(self.width);
-        let x = i - y * self.width;
-        (x,y)
+        let row = i.div(self.width);
+        let x = i - row * self.width;
+        (x, row - self.buffer)
     }
 
+    /// Color of the block at `(x, y)`, which has to be on the field.
     pub fn value_of(&self, x: i32, y: i32) -> Option<Color> {
-        self.game_field[self.index_of(x,y)]
+        self.game_field[self.index_of(x,y)].map(|block| block.color)
+    }
+
+    /// The block at `(x, y)`, `None` for empty cells and cells off the field.
+    pub fn block_at(&self, x: i32, y: i32) -> Option<Block> {
+        if self.valid_index(x,y) {
+            self.game_field[self.index_of(x,y)]
+        } else {
+            Option::None
+        }
     }
 
+    /// Whether there's a block at `(x, y)`.
     pub fn contains_node(&self, x: i32, y: i32) -> bool {
         self.valid_index(x,y) && self.value_of(x,y).is_some()
     }
 
+    /// Whether there's a block at any of `nodes`.
     pub fn contains_any(&self, nodes : &[(i32,i32)]) -> bool {
         nodes.iter().any(|(x,y)| {self.contains_node(*x,*y)})
     }
 
+    /// Whether the piece fits inside the walls and floor without overlapping
+    /// any locked block. Cells above the buffer zone are allowed.
     pub fn valid_piece(&self, piece: Piece) -> bool {
         let blocks = piece.coordinates();
-        return !self.contains_any(&blocks)
+        !self.contains_any(&blocks)
             && !self.hits_wall(&blocks)
             && !self.hits_floor(&blocks)
     }
 
+    /// Whether any of `nodes` is below the bottom row.
     pub fn hits_floor(&self, nodes : &[(i32,i32)]) -> bool {
         nodes.iter().any(|(_,y)| {*y >= self.height})
     }
 
+    /// Whether any of `nodes` is left or right of the field.
     pub fn hits_wall(&self, nodes : &[(i32,i32)]) -> bool {
         nodes.iter().any(|(x,_)| {*x >= self.width || *x<0})
     }
 
+    /// The cells of `nodes` that have a block.
     pub fn collisions(&self, nodes : &[(i32,i32)]) -> Vec<(i32,i32)> {
-        let res : Vec<(i32,i32)>;
-        res = nodes.iter()
-            .filter(|(x,y)| self.contains_node(*x,*y))
-            .map(|e|{*e}).collect();
+        let res : Vec<(i32,i32)> = nodes.iter()
+            .filter(|(x,y)| self.contains_node(*x,*y)).copied().collect();
         res
     }
 
+    /// Whether there are no blocks at all, buffer rows included.
+    pub fn is_empty(&self) -> bool {
+        self.game_field.iter().all(|block| block.is_none())
+    }
+
+    /// Whether every cell of `row` has a block.
     pub fn is_row_full(&self, row: i32) -> bool {
         for x in 0 .. self.width {
             if !self.contains_node(x, row) {
@@ -87,29 +240,40 @@ impl GameField {
         true
     }
 
+    /// Gives every block the color `skin` has for its kind.
+    pub fn recolor(&mut self, skin: &Skin) {
+        for block in self.game_field.iter_mut() {
+            *block = block.map(|b| b.recolored(skin));
+        }
+    }
+
+    /// Every locked block, including those in the buffer zone.
     pub fn get_blocks(&self) -> Vec<((i32,i32), Color)> {
         let mut res = Vec::new();
         for i in 0 .. self.game_field.len() {
             match self.game_field[i] {
                 Option::None => (),
-                Option::Some(color) => {
-                    res.push((self.from_index(i as i32), color))
+                Option::Some(block) => {
+                    res.push((self.coordinates_of(i as i32), block.color))
                 }
             }
         };
         res
     }
 
-    pub fn insert_blocks(&mut self, blocks: &[(i32,i32)], color: Color) {
+    /// Puts `block` in every empty cell of `blocks`.
+    pub fn insert_blocks(&mut self, blocks: &[(i32,i32)], block: Block) {
         for (x,y) in blocks {
             if !self.contains_node(*x,*y) {
-                self.set_block(*x,*y, color)
+                self.set_block(*x,*y, block)
             }
         }
     }
 
+    /// Removes a row and moves every row above it, buffer rows included,
+    /// down by one.
     pub fn delete_row(&mut self, row: i32) {
-        let iter_start = (self.width * (row + 1) - 1) as usize;
+        let iter_start = self.index_of(self.width - 1, row);
         let iter_end = self.width as usize;
         for i in (iter_end .. (iter_start + 1)).rev()  {
             self.game_field[i] = self.game_field[i - self.width as usize]
@@ -119,11 +283,148 @@ impl GameField {
         }
     }
 
+    /// Flips every row left to right.
+    pub fn mirror(&mut self) {
+        for y in -self.buffer .. self.height {
+            for x in 0 .. self.width / 2 {
+                let (left, right) = (self.index_of(x, y), self.index_of(self.width - 1 - x, y));
+                self.game_field.swap(left, right);
+            }
+        }
+    }
+
+    /// Every full row, buffer rows included, from the top down.
+    pub fn full_rows(&self) -> Vec<i32> {
+        (-self.buffer .. self.height).filter(|row| self.is_row_full(*row)).collect()
+    }
+
+    /// Removes `rows` and lets the blocks above come down by `gravity`.
+    /// Sticky and cascade gravity can leave new full rows behind.
+    pub fn collapse(&mut self, rows: &[i32], gravity: ClearGravity) {
+        if gravity == ClearGravity::Naive {
+            let mut rows = rows.to_vec();
+            rows.sort_unstable();
+            for row in rows {
+                self.delete_row(row);
+            }
+            return
+        }
+        for row in rows {
+            for x in 0 .. self.width {
+                self.clear_block(x, *row);
+            }
+        }
+        // Groups move one row at a time, lowest first, and are found again
+        // after every round since landing groups can join
+        loop {
+            let mut moved = false;
+            for group in self.groups(gravity) {
+                moved |= self.fall_group(&group);
+            }
+            if !moved {
+                return
+            }
+        }
+    }
+
+    // Cells that fall together: one group per block under cascade gravity,
+    // blocks touching on a side under sticky gravity. Sorted so the lowest
+    // group comes first
+    fn groups(&self, gravity: ClearGravity) -> Vec<Vec<(i32,i32)>> {
+        let mut cells: Vec<(i32,i32)> = self.get_blocks().iter().map(|(cell,_)| *cell).collect();
+        let mut groups = Vec::new();
+        while let Option::Some(start) = cells.pop() {
+            let mut group = vec![start];
+            let mut i = 0;
+            while gravity == ClearGravity::Sticky && i < group.len() {
+                let (x,y) = group[i];
+                for next in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)].iter() {
+                    if let Option::Some(found) = cells.iter().position(|cell| cell == next) {
+                        group.push(cells.swap_remove(found));
+                    }
+                }
+                i += 1;
+            }
+            groups.push(group);
+        }
+        groups.sort_by_key(|group| -group.iter().map(|(_,y)| *y).max().unwrap_or(0));
+        groups
+    }
+
+    // Moves a group down one row if every cell under it is free or part of it
+    fn fall_group(&mut self, group: &[(i32,i32)]) -> bool {
+        let free = group.iter().all(|(x,y)| {
+            let below = (*x, y + 1);
+            group.contains(&below)
+                || !self.hits_floor(&[below]) && !self.contains_node(below.0, below.1)
+        });
+        if !free {
+            return false
+        }
+        let blocks: Vec<Block> = group.iter()
+            .filter_map(|(x,y)| self.block_at(*x, *y))
+            .collect();
+        for (x,y) in group {
+            self.clear_block(*x, *y);
+        }
+        for ((x,y), block) in group.iter().zip(blocks) {
+            self.set_block(*x, y + 1, block);
+        }
+        true
+    }
+
+    /// Number of columns.
     pub fn width(&self) -> i32 {
         self.width
     }
 
+    /// Number of visible rows.
     pub fn height(&self) -> i32 {
         self.height
     }
+
+    /// Number of rows in the buffer zone above the visible rows.
+    pub fn buffer(&self) -> i32 {
+        self.buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::notation::Setup;
+
+    fn field(rows: &str) -> GameField {
+        rows.parse::<Setup>().unwrap().field
+    }
+
+    #[test]
+    fn naive_gravity_moves_everything_above_down_a_row() {
+        let mut game_field = field(".#..\n....\n####\n#..#");
+        game_field.collapse(&[2], ClearGravity::Naive);
+        assert_eq!(game_field, field("....\n.#..\n....\n#..#"));
+    }
+
+    #[test]
+    fn sticky_gravity_drops_hanging_blocks_until_they_land() {
+        let mut game_field = field(".#..\n....\n####\n#..#");
+        game_field.collapse(&[2], ClearGravity::Sticky);
+        assert_eq!(game_field, field("....\n....\n....\n##.#"));
+    }
+
+    #[test]
+    fn sticky_gravity_keeps_touching_blocks_together() {
+        let mut game_field = field(".##.\n##.#\n####");
+        game_field.collapse(&[2], ClearGravity::Sticky);
+        assert_eq!(game_field, field("....\n.##.\n##.#"));
+        assert!(game_field.full_rows().is_empty());
+    }
+
+    #[test]
+    fn cascade_gravity_can_fill_a_row_for_the_chain() {
+        let mut game_field = field(".##.\n##.#\n####");
+        game_field.collapse(&[2], ClearGravity::Cascade);
+        assert_eq!(game_field, field("....\n.#..\n####"));
+        assert_eq!(game_field.full_rows(), vec![2]);
+    }
 }
@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+
+/// Frontend independent input actions. Every frontend maps its own key events
+/// onto these and hands them to `TetrisManager::apply`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    /// Shifts a column to the left.
+    MoveLeft,
+    /// Shifts a column to the right.
+    MoveRight,
+    /// Shifts all the way to the left, like holding the key past DAS.
+    DasLeft,
+    /// Shifts all the way to the right.
+    DasRight,
+    /// Moves a row down, locking the piece if it rests on the stack.
+    SoftDrop,
+    /// Drops the piece to the bottom and locks it.
+    HardDrop,
+    /// Turns the piece clockwise.
+    RotateRight,
+    /// Turns the piece counterclockwise.
+    RotateLeft,
+    /// Turns the piece around in one step.
+    Rotate180,
+    /// Swaps the piece with the held one.
+    Hold,
+}
+
+/// Turns a held direction key into shifts the way consoles do: one step as
+/// the key goes down, then all the way to the wall once it was held for
+/// `Timing::das` frames.
+///
+/// For frontends that see key releases. Repeated presses of a held key,
+/// like the system's key repeat, are ignored.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Das {
+    // The held direction and frames it was held for
+    held: Option<(Action, u32)>,
+}
+
+impl Das {
+    /// No key held.
+    pub fn new() -> Das {
+        Das::default()
+    }
+
+    /// A key went down, returns the action to apply. Only `MoveLeft` and
+    /// `MoveRight` start charging, anything else passes through.
+    pub fn press(&mut self, action: Action) -> Option<Action> {
+        match action {
+            Action::MoveLeft | Action::MoveRight => {
+                if self.held.is_some_and(|(held, _)| held == action) {
+                    return Option::None
+                }
+                self.held = Option::Some((action, 0));
+                Option::Some(action)
+            },
+            action => Option::Some(action)
+        }
+    }
+
+    /// A key went up, stops charging if it was the held direction.
+    pub fn release(&mut self, action: Action) {
+        if self.held.is_some_and(|(held, _)| held == action) {
+            self.held = Option::None;
+        }
+    }
+
+    /// Counts a frame with the key still down. Returns `DasLeft` or
+    /// `DasRight` on every frame from the one the charge reaches `das`, so
+    /// pieces that spawn while the key is held go to the wall as well.
+    pub fn frame(&mut self, das: u32) -> Option<Action> {
+        let (action, frames) = self.held.as_mut()?;
+        *frames = frames.saturating_add(1);
+        if *frames < das.max(1) {
+            return Option::None
+        }
+        match action {
+            Action::MoveLeft => Option::Some(Action::DasLeft),
+            _ => Option::Some(Action::DasRight),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::config::GameConfig;
+    use crate::tetris::manager::TetrisManager;
+
+    #[test]
+    fn holding_a_direction_shifts_once_then_to_the_wall() {
+        let mut das = Das::new();
+        assert_eq!(das.press(Action::MoveLeft), Option::Some(Action::MoveLeft));
+        assert_eq!(das.press(Action::MoveLeft), Option::None);
+        let shifts: Vec<Option<Action>> = (0 .. 12).map(|_| das.frame(10)).collect();
+        assert!(shifts[.. 9].iter().all(Option::is_none));
+        assert!(shifts[9 ..].iter().all(|shift| *shift == Option::Some(Action::DasLeft)));
+    }
+
+    #[test]
+    fn a_charged_key_takes_the_next_piece_to_the_wall_too() {
+        let mut manager = TetrisManager::with_config(&GameConfig::default().are(10).seed(3));
+        let mut das = Das::new();
+        let frame = |manager: &mut TetrisManager, das: &mut Das| {
+            if let Option::Some(shift) = das.frame(10) {
+                manager.apply(shift);
+            }
+            manager.frame();
+        };
+        let at_wall = |manager: &TetrisManager| {
+            !manager.field().valid_piece(manager.current().move_left())
+        };
+        manager.apply(das.press(Action::MoveLeft).unwrap());
+        (0 .. 10).for_each(|_| frame(&mut manager, &mut das));
+        assert!(at_wall(&manager));
+
+        manager.apply(Action::HardDrop);
+        while manager.active().is_none() {
+            frame(&mut manager, &mut das);
+        }
+        assert!(at_wall(&manager));
+        das.release(Action::MoveLeft);
+        assert_eq!(das.frame(10), Option::None);
+    }
+
+    #[test]
+    fn releasing_or_switching_restarts_the_charge() {
+        let mut das = Das::new();
+        das.press(Action::MoveRight);
+        (0 .. 5).for_each(|_| assert_eq!(das.frame(8), Option::None));
+        das.release(Action::MoveRight);
+        assert_eq!(das.frame(8), Option::None);
+
+        das.press(Action::MoveRight);
+        das.frame(8);
+        assert_eq!(das.press(Action::MoveLeft), Option::Some(Action::MoveLeft));
+        das.release(Action::MoveRight);
+        let shift = (0 .. 8).filter_map(|_| das.frame(8)).next();
+        assert_eq!(shift, Option::Some(Action::DasLeft));
+    }
+
+    #[test]
+    fn other_keys_pass_through() {
+        let mut das = Das::new();
+        assert_eq!(das.press(Action::HardDrop), Option::Some(Action::HardDrop));
+        assert_eq!(das.press(Action::HardDrop), Option::Some(Action::HardDrop));
+        assert_eq!(das.frame(1), Option::None);
+    }
+}
@@ -1,114 +1,1189 @@
-use crate::tetris::gamefield::GameField;
-use crate::tetris::piece::Piece;
+use std::collections::VecDeque;
+use std::time::Duration;
+use crate::tetris::gamefield::{Block, ClearGravity, GameField};
+use crate::tetris::piece::{Piece, PieceType, Rotation};
 use crate::tetris::color::Color;
-use crate::graphics::renderer::Renderer;
-use rand::thread_rng;
+use crate::tetris::queue::PieceQueue;
+use crate::tetris::input::Action;
+use crate::tetris::modifier::Modifier;
+use crate::tetris::config::{GameConfig, Mode};
+use crate::tetris::event::{Event, TSpin};
+use crate::tetris::finesse::{self, FinesseStats, PieceFinesse};
+use crate::tetris::notation::{letter_color, Setup};
+use crate::tetris::rotation::{self, RotationSystem};
+use crate::tetris::skin::Skin;
+use crate::tetris::timing::{Timing, SONIC_GRAVITY};
 
+/// Length of one frame, the unit for delays.
+pub const FRAME : Duration = Duration::from_micros(16_667);
 
+// Inputs that act on a piece as it spawns when pressed during the entry delay
+fn initial_action(action: Action) -> bool {
+    matches!(action, Action::Hold | Action::RotateRight | Action::RotateLeft | Action::Rotate180)
+}
+
+// Base points for clearing 0 to 4 rows at once, multiplied by one plus a
+// tenth of the lines. Bigger pieces can clear more, those score like four
+const LINE_SCORES : [u32; 5] = [0, 100, 300, 500, 800];
+
+// Master mode ends at this level
+const MASTER_LEVELS : u32 = 999;
+
+// Snapshots of the game taken as each piece started falling, for undo in
+// practice mode. Redo goes back to the exact moment undo was pressed, with
+// the snapshot of that piece's start
+#[derive(Clone, Default)]
+struct History {
+    depth: usize,
+    start: Option<Box<TetrisManager>>,
+    undo: VecDeque<TetrisManager>,
+    redo: Vec<(TetrisManager, Option<Box<TetrisManager>>)>,
+}
+
+/// A single game: the field, the falling piece, queue, hold and score.
+///
+/// Frontends feed it `Action`s and call `frame` sixty times a second for
+/// gravity, delays and locking, then read the state back for drawing and
+/// `drain_events` for anything that happened.
+#[derive(Clone)]
 pub struct TetrisManager {
+    seed: u64,
+    mode: Mode,
+    system: &'static dyn RotationSystem,
+    skin: Skin,
     game_field: GameField,
     game_piece: Piece,
-    rng: rand::rngs::ThreadRng,
-    i: i32
+    queue: PieceQueue,
+    hold: Option<Piece>,
+    hold_used: bool,
+    // Whether the last successful move was a rotation, for T-spin detection
+    rotated_last: bool,
+    // The active piece as it spawned and the inputs pressed since, for finesse
+    spawned: Piece,
+    inputs: u32,
+    // Direction of the taps in a row so far and the inputs before them. Key
+    // repeat reaching the wall counts as a single DAS input
+    shifting: Option<(Action, u32)>,
+    finesse: FinesseStats,
+    modifiers: Vec<Modifier>,
+    // Frames played and pieces locked, for the modifiers
+    frames: u64,
+    pieces: u32,
+    // Timing of modes without their own tables
+    base: Timing,
+    // Level of master mode, which also counts pieces
+    level: u32,
+    // Rows of gravity not fallen yet, and frames the piece rested on the stack
+    fall: f32,
+    lock_frames: u32,
+    cleared_last: u32,
+    // Full rows still shown during the line clear delay, the frames left of
+    // it and the link of the chain that cleared them
+    clear_gravity: ClearGravity,
+    clearing: Vec<i32>,
+    clear_left: u32,
+    chain: u32,
+    // Frames left of the entry delay while waiting for the next piece, the
+    // line clear delay is part of it
+    entry: Option<u32>,
+    // Inputs pressed during the entry delay, played once the piece spawns
+    buffered: Vec<Action>,
+    score: u32,
+    lines: u32,
+    game_over: bool,
+    history: History,
+    events: Vec<Event>,
 }
 
 impl TetrisManager {
+    /// Starts a game on a `width` x `height` field with default settings.
     pub fn new(width: i32, height: i32) -> TetrisManager {
-        let mut rng = rand::thread_rng();
-        let mut piece = Piece::from_rng(&mut rng);
-        piece.set_position((width / 2, 0));
+        TetrisManager::with_config(
+            &GameConfig::default().width(width).height(height)
+        )
+    }
+
+    /// Starts a game with the settings in `config`, with a random seed unless
+    /// it sets one.
+    pub fn with_config(config: &GameConfig) -> TetrisManager {
+        let seed = config.seed.unwrap_or_else(rand::random);
+        // Big pieces play on a field of half the size, drawn twice as large
+        let scale = if config.modifiers.contains(&Modifier::Big) { 2 } else { 1 };
+        let mut manager = TetrisManager::start(
+            seed,
+            GameField::with_buffer(config.width / scale, config.height / scale, config.buffer / scale),
+            PieceQueue::new(seed, config.preview, config.piece_set.clone(), config.skin.clone()),
+            config.rotation_system,
+            Option::None
+        );
+        manager.mode = config.mode;
+        manager.skin = config.skin.clone();
+        manager.base = Timing {
+            are: config.are,
+            line_are: config.are,
+            line_clear: config.line_clear,
+            .. Timing::default()
+        };
+        manager.clear_gravity = config.clear_gravity;
+        manager.modifiers = config.modifiers.clone();
+        if config.mode == Mode::Practice {
+            manager.history.depth = config.undo_depth;
+        }
+        manager.begin(Option::None);
+        manager
+    }
+
+    /// Starts from a fixed position, with the rotation system, buffer rows,
+    /// preview and skin of `config`. Only the pieces in the setup are dealt,
+    /// the game ends with `Event::OutOfPieces` once they are used up.
+    pub fn from_setup(setup: &Setup, config: &GameConfig) -> TetrisManager {
+        let as_piece = |piece_type: PieceType| {
+            Piece::new(piece_type, letter_color(piece_type), (0,0))
+        };
+        let queue = PieceQueue::fixed(
+            setup.queue.iter().cloned().map(as_piece).collect(),
+            config.preview
+        );
+        let mut manager = TetrisManager::start(
+            0,
+            setup.field.resized_buffer(config.buffer),
+            queue,
+            config.rotation_system,
+            setup.hold.map(as_piece)
+        );
+        manager.begin(setup.active.map(|piece| piece.with_system(config.rotation_system)));
+        manager.set_skin(config.skin.clone());
+        manager
+    }
 
+    fn start(
+        seed: u64,
+        field: GameField,
+        queue: PieceQueue,
+        system: &'static dyn RotationSystem,
+        hold: Option<Piece>
+    ) -> TetrisManager {
         TetrisManager {
-            game_field: GameField::new(width, height),
-            game_piece: piece,
-            rng: rng,
-            i:0
+            seed,
+            mode: Mode::Marathon,
+            system,
+            skin: Skin::default(),
+            game_field: field,
+            // Replaced by `begin`, either by the given piece or the queue
+            game_piece: Piece::new(PieceType::O, Color::GRAY, (0,0)),
+            queue,
+            hold,
+            hold_used: false,
+            rotated_last: false,
+            spawned: Piece::new(PieceType::O, Color::GRAY, (0,0)),
+            inputs: 0,
+            shifting: Option::None,
+            finesse: FinesseStats::default(),
+            modifiers: Vec::new(),
+            frames: 0,
+            pieces: 0,
+            base: Timing::default(),
+            level: 0,
+            fall: 0.,
+            lock_frames: 0,
+            cleared_last: 0,
+            clear_gravity: ClearGravity::Naive,
+            clearing: Vec::new(),
+            clear_left: 0,
+            chain: 0,
+            entry: Option::None,
+            buffered: Vec::new(),
+            score: 0,
+            lines: 0,
+            game_over: false,
+            history: History::default(),
+            events: Vec::new(),
+        }
+    }
+
+    // Starts with the given piece or the first from the queue, once the mode
+    // and timing are set
+    fn begin(&mut self, active: Option<Piece>) {
+        match active {
+            Option::Some(piece) => {
+                self.game_piece = piece;
+                self.spawned = piece;
+                self.events.push(Event::Spawned(piece.piece_type()));
+            },
+            Option::None => self.next_piece()
+        };
+        self.checkpoint();
+    }
+
+    // Pieces spawn with their lowest blocks in the first row above the
+    // visible field, shifted by the spawn offset of their definition, then
+    // drop one row right away if nothing is in the way
+    fn spawn(&mut self, piece: Piece) {
+        let piece = piece.with_system(self.system);
+        let (dx, dy) = piece.def().spawn;
+        let x = self.game_field.width() / 2 + dx;
+        let mut placed = piece.with_rotation(Rotation::Up);
+        placed.set_position((x, 0));
+        // Pieces that would stick out of a narrow field are pushed back in
+        let width = self.game_field.width();
+        let right = placed.coordinates().iter().map(|(x,_)| *x).max().unwrap_or(0);
+        if right >= width {
+            placed = placed.moved_by(width - 1 - right, 0);
+        }
+        let left = placed.coordinates().iter().map(|(x,_)| *x).min().unwrap_or(0);
+        if left < 0 {
+            placed = placed.moved_by(-left, 0);
+        }
+        let bottom = placed.coordinates().iter().map(|(_,y)| *y).max().unwrap_or(0);
+        self.game_piece = placed.moved_by(0, -1 - bottom + dy);
+        self.rotated_last = false;
+        self.inputs = 0;
+        self.shifting = Option::None;
+        self.fall = 0.;
+        self.lock_frames = 0;
+        if !self.game_field.valid_piece(self.game_piece) {
+            self.spawned = self.game_piece;
+            self.top_out();
+            return
+        }
+        if self.game_field.valid_piece(self.game_piece.move_down()) {
+            self.game_piece = self.game_piece.move_down();
+        }
+        self.spawned = self.game_piece;
+        self.events.push(Event::Spawned(piece.piece_type()));
+        self.settle();
+    }
+
+    // At 20G the piece is always on the floor, right after spawning and
+    // after every input
+    fn settle(&mut self) {
+        if self.timing().gravity >= SONIC_GRAVITY {
+            while self.descend() {}
+        }
+    }
+
+    fn top_out(&mut self) {
+        self.game_over = true;
+        self.events.push(Event::GameOver);
+    }
+
+    fn lock(&mut self) {
+        let coords = self.game_piece.coordinates();
+        // Locking entirely inside the buffer zone, or partly above it, is a
+        // top out
+        let buffer = self.game_field.buffer();
+        if coords.iter().all(|(_,y)| *y < 0) || coords.iter().any(|(_,y)| *y < -buffer) {
+            self.top_out();
+            return
+        }
+        let tspin = self.tspin();
+        self.judge_finesse();
+        let block = Block { locked: self.frames, .. Block::of_piece(&self.game_piece) };
+        self.game_field.insert_blocks(&coords, block);
+        if let Option::Some(start) = self.history.start.take() {
+            self.history.undo.push_back(*start);
+            if self.history.undo.len() > self.history.depth {
+                self.history.undo.pop_front();
+            }
+            self.history.redo.clear();
+        }
+        self.events.push(Event::Locked(self.game_piece.piece_type()));
+
+        let rows = self.game_field.full_rows();
+        let cleared = rows.len() as u32;
+        let perfect = cleared > 0 && self.game_field.get_blocks().iter()
+            .all(|((_,y),_)| rows.contains(y));
+        self.cleared_last = cleared;
+        self.chain = 1;
+        if let Option::Some(kind) = tspin {
+            self.events.push(Event::TSpin(kind, cleared));
+        }
+        if perfect {
+            self.events.push(Event::PerfectClear);
+        }
+        if cleared > 0 {
+            self.clear(rows);
+        }
+        self.hold_used = false;
+        self.pieces += 1;
+        let mirror = self.modifiers.iter().filter_map(|modifier| match modifier {
+            Modifier::Mirror(pieces) => Option::Some(pieces.get()),
+            _ => Option::None
+        }).next();
+        if mirror.is_some_and(|every| self.pieces.is_multiple_of(every)) {
+            self.game_field.mirror();
+        }
+        self.check_goals();
+    }
+
+    // Scores full rows and keeps them on the field for the line clear delay,
+    // or collapses them right away without one. Later links of a chain score
+    // as many times more
+    fn clear(&mut self, rows: Vec<i32>) {
+        let cleared = rows.len() as u32;
+        self.events.push(Event::LinesCleared(cleared));
+        self.score += LINE_SCORES[(cleared as usize).min(4)] * (self.lines / 10 + 1) * self.chain;
+        self.lines += cleared;
+        if self.mode == Mode::Master {
+            self.level = (self.level + cleared).min(MASTER_LEVELS);
+        }
+        self.clearing = rows;
+        self.clear_left = self.timing().line_clear;
+        if self.clear_left == 0 {
+            self.collapse();
+        }
+    }
+
+    // Removes the cleared rows. Blocks landing after sticky or cascade
+    // gravity can fill more rows, which clear as the next link of the chain
+    fn collapse(&mut self) {
+        let rows = std::mem::take(&mut self.clearing);
+        self.game_field.collapse(&rows, self.clear_gravity);
+        let next = self.game_field.full_rows();
+        if !next.is_empty() {
+            self.chain += 1;
+            self.events.push(Event::Chain(self.chain));
+            self.clear(next);
+        } else {
+            self.events.push(Event::ChainEnded(self.chain));
+        }
+    }
+
+    fn check_goals(&mut self) {
+        if self.mode == Mode::Master && self.level == MASTER_LEVELS {
+            self.finish();
+        }
+        if self.mode.line_goal().is_some_and(|goal| self.lines >= goal) {
+            self.finish();
         }
     }
 
+    /// Ends the game because the mode's goal was reached, for goals the
+    /// manager can't check itself like time limits.
+    pub fn finish(&mut self) {
+        if !self.game_over {
+            self.game_over = true;
+            self.events.push(Event::Finished);
+        }
+    }
+
+    // Compares the inputs for the piece about to lock with the fewest that
+    // would have placed it, including the hold press if hold was used
+    fn judge_finesse(&mut self) {
+        let optimal = finesse::optimal_inputs(
+            &self.game_field,
+            self.spawned,
+            &self.game_piece.coordinates()
+        );
+        if let Option::Some(optimal) = optimal {
+            let piece = PieceFinesse {
+                piece_type: self.game_piece.piece_type(),
+                inputs: self.inputs,
+                optimal: optimal + self.hold_used as u32,
+            };
+            if piece.faults() > 0 {
+                self.events.push(Event::FinesseFault(piece.piece_type, piece.faults()));
+            }
+            self.finesse.record(piece);
+        }
+    }
+
+    /// Moves the active piece down one row, locking it if it can't move.
+    /// Returns whether the piece moved.
     pub fn step(&mut self) -> bool {
+        if self.descend() {
+            return true
+        }
+        self.lock();
+        false
+    }
+
+    // Moves the active piece down one row if nothing is in the way, which
+    // restarts the lock delay
+    fn descend(&mut self) -> bool {
         let down_one = self.game_piece.move_down();
-        let new_coords = down_one.coordinates();
-        let hits_floor = self.game_field.hits_floor(&new_coords);
-        let hits_block = self.game_field.contains_any(new_coords.as_ref());
-        if hits_floor || hits_block {
-            self.game_field.insert_blocks(
-                &self.game_piece.coordinates(),
-                self.game_piece.color()
-            );
-            let mut rows: Vec<i32> = new_coords.iter().map(|(_,y)| *y)
-                .collect();
-            rows.sort_unstable();
-            rows.dedup();
-            for row in rows {
-                if (self.game_field.is_row_full(row)){
-                    self.game_field.delete_row(row);
-                }
-            };
+        if !self.game_field.valid_piece(down_one) {
             return false
-        } else {
-            self.game_piece = down_one;
-            return true
         }
+        self.game_piece = down_one;
+        self.rotated_last = false;
+        self.lock_frames = 0;
+        true
     }
 
-    pub fn rotate_right(&mut self) {
-        let rotated = self.game_piece.rotate_right();
+    // Three corner rule: a T locked right after a rotation with at least three
+    // of the cells diagonal to its center blocked. It's a full T-spin when
+    // both corners on the pointing side are blocked, a mini otherwise.
+    fn tspin(&self) -> Option<TSpin> {
+        if self.game_piece.piece_type() != PieceType::T || !self.rotated_last {
+            return Option::None
+        }
+        // Found from the cells since not every rotation system turns the T
+        // around its center: the center has neighbors on three sides and
+        // the nub points away from the fourth
+        let cells = self.game_piece.coordinates();
+        let has = |x: i32, y: i32| cells.contains(&(x, y));
+        let sides = [(0,-1),(1,0),(0,1),(-1,0)];
+        let (x,y) = *cells.iter().find(|(x,y)| {
+            sides.iter().filter(|(dx,dy)| has(x + dx, y + dy)).count() == 3
+        })?;
+        let (nx,ny) = *sides.iter()
+            .find(|(dx,dy)| has(x + dx, y + dy) && !has(x - dx, y - dy))?;
+        let blocked = |(dx,dy): &(i32,i32)| {
+            let (cx, cy) = (x + dx, y + dy);
+            self.game_field.hits_wall(&[(cx, cy)])
+                || self.game_field.hits_floor(&[(cx, cy)])
+                || self.game_field.contains_node(cx, cy)
+        };
+        let corners = [(-1,-1),(1,-1),(1,1),(-1,1)];
+        if corners.iter().filter(|c| blocked(c)).count() < 3 {
+            return Option::None
+        }
+        let front = [(nx - ny, ny + nx), (nx + ny, ny - nx)];
+        if front.iter().all(blocked) {
+            Option::Some(TSpin::Full)
+        } else {
+            Option::Some(TSpin::Mini)
+        }
+    }
 
-        if self.game_field.valid_piece(rotated) {
-            self.game_piece = rotated;
+    fn rotate_to(&mut self, to: Rotation) {
+        let turned = rotation::rotate(&self.game_field, self.game_piece, to);
+        if let Option::Some(piece) = turned {
+            self.game_piece = piece;
+            self.rotated_last = true;
         }
     }
 
+    /// Turns the falling piece clockwise, trying the kicks of its rotation
+    /// system.
+    pub fn rotate_right(&mut self) {
+        self.rotate_to(self.game_piece.rotation().rotate_right());
+    }
+
+    /// Turns the falling piece counterclockwise, trying the kicks of its
+    /// rotation system.
     pub fn rotate_left(&mut self) {
-        let rotated = self.game_piece.rotate_left();
-        if self.game_field.valid_piece(rotated) {
-            self.game_piece = rotated.clone();
-        }
+        self.rotate_to(self.game_piece.rotation().rotate_left());
+    }
+
+    /// Turns the falling piece around, trying the kicks of its rotation
+    /// system.
+    pub fn rotate_180(&mut self) {
+        self.rotate_to(self.game_piece.rotation().rotate_180());
     }
 
+    /// Shifts the falling piece a column left if there's room.
     pub fn move_left(&mut self) {
         let moved = self.game_piece.move_left();
         if self.game_field.valid_piece(moved) {
             self.game_piece = moved;
+            self.rotated_last = false;
         }
     }
 
+    /// Shifts the falling piece a column right if there's room.
     pub fn move_right(&mut self) {
         let moved = self.game_piece.move_right();
         if self.game_field.valid_piece(moved) {
             self.game_piece = moved;
+            self.rotated_last = false;
+        }
+    }
+
+    /// Shifts the active piece left until it hits a wall or the stack.
+    pub fn das_left(&mut self) {
+        let mut moved = self.game_piece;
+        while self.game_field.valid_piece(moved.move_left()) {
+            moved = moved.move_left();
+        }
+        if moved != self.game_piece {
+            self.game_piece = moved;
+            self.rotated_last = false;
         }
     }
 
+    /// Shifts the active piece right until it hits a wall or the stack.
+    pub fn das_right(&mut self) {
+        let mut moved = self.game_piece;
+        while self.game_field.valid_piece(moved.move_right()) {
+            moved = moved.move_right();
+        }
+        if moved != self.game_piece {
+            self.game_piece = moved;
+            self.rotated_last = false;
+        }
+    }
+
+    /// Applies one row of gravity, spawning the next piece after a lock.
+    /// Does nothing during the entry delay.
     pub fn tick(&mut self) {
+        if self.game_over || self.entry.is_some() {
+            return
+        }
         let stepped = self.step();
         if !stepped {
-            self.game_piece = Piece::from_rng(&mut self.rng);
-            self.game_piece.set_position((self.game_field.width() / 2, 0));
+            self.after_lock();
+        }
+    }
+
+    /// Advances the game by one frame, a sixtieth of a second. Counts down
+    /// the entry delay and spawns the next piece once it's over, otherwise
+    /// applies gravity and locks a piece that rested on the stack for the
+    /// lock delay.
+    pub fn frame(&mut self) {
+        if self.game_over {
+            return
+        }
+        self.frames += 1;
+        match self.entry {
+            Option::Some(_) if !self.clearing.is_empty() => self.count_clear(),
+            Option::Some(left) if left > 1 => self.entry = Option::Some(left - 1),
+            Option::Some(_) => self.end_entry(),
+            Option::None => self.apply_gravity()
+        }
+    }
+
+    // The entry delay after a clear only starts once the rows are gone
+    fn count_clear(&mut self) {
+        self.clear_left -= 1;
+        if self.clear_left > 0 {
+            return
+        }
+        self.collapse();
+        self.check_goals();
+        if !self.game_over && self.clearing.is_empty() && self.entry == Option::Some(0) {
+            self.end_entry();
+        }
+    }
+
+    fn apply_gravity(&mut self) {
+        let timing = self.timing();
+        if timing.gravity >= SONIC_GRAVITY {
+            self.settle();
+        } else {
+            // Sums like sixty times 1/60 come out just short of a whole row
+            self.fall += timing.gravity;
+            while self.fall >= 1. - 1e-4 {
+                self.fall -= 1.;
+                if !self.descend() {
+                    self.fall = 0.;
+                }
+            }
+        }
+        if self.game_field.valid_piece(self.game_piece.move_down()) {
+            return
+        }
+        self.lock_frames += 1;
+        if self.lock_frames >= timing.lock_delay {
+            self.lock();
+            self.after_lock();
+        }
+    }
+
+    // Counts the piece towards the master level, except on the last level
+    // of a section which only a line clear leaves. The next piece comes
+    // right away without an entry delay or rows left to clear
+    fn after_lock(&mut self) {
+        if self.game_over {
+            return
+        }
+        if self.mode == Mode::Master && self.level % 100 != 99 && self.level != MASTER_LEVELS - 1 {
+            self.level += 1;
+        }
+        let delay = self.timing().entry_delay(self.cleared_last);
+        if delay == 0 && self.clearing.is_empty() {
+            self.next_piece();
+            self.checkpoint();
+        } else {
+            self.entry = Option::Some(delay);
+        }
+    }
+
+    // Remembers the game as the new piece starts falling, it's what undo
+    // goes back to once the piece locks
+    fn checkpoint(&mut self) {
+        if self.history.depth > 0 {
+            self.history.start = Option::Some(Box::new(self.snapshot()));
+        }
+    }
+
+    // A copy of the game without its history or pending events
+    fn snapshot(&mut self) -> TetrisManager {
+        let history = std::mem::take(&mut self.history);
+        let events = std::mem::take(&mut self.events);
+        let snapshot = self.clone();
+        self.history = history;
+        self.events = events;
+        snapshot
+    }
+
+    // Goes back to `snapshot`, keeping the history, pending events and skin
+    fn restore(&mut self, snapshot: TetrisManager) {
+        let history = std::mem::take(&mut self.history);
+        let events = std::mem::take(&mut self.events);
+        let skin = self.skin.clone();
+        *self = snapshot;
+        self.history = history;
+        self.events = events;
+        if self.skin != skin {
+            self.set_skin(skin);
+        }
+    }
+
+    /// Goes back to when the last placed piece started falling, with the
+    /// field, queue, hold and score it had then. Only in practice mode,
+    /// returns whether there was anything to undo.
+    pub fn undo(&mut self) -> bool {
+        let previous = match self.history.undo.pop_back() {
+            Option::Some(previous) => previous,
+            Option::None => return false
+        };
+        let now = self.snapshot();
+        let start = self.history.start.take();
+        self.history.redo.push((now, start));
+        self.restore(previous.clone());
+        self.history.start = Option::Some(Box::new(previous));
+        self.events.push(Event::Undone);
+        true
+    }
+
+    /// Takes back the last undo, returning to the moment it was made.
+    pub fn redo(&mut self) -> bool {
+        let (state, start) = match self.history.redo.pop() {
+            Option::Some(redo) => redo,
+            Option::None => return false
+        };
+        if let Option::Some(previous) = self.history.start.take() {
+            self.history.undo.push_back(*previous);
+        }
+        self.restore(state);
+        self.history.start = start;
+        self.events.push(Event::Redone);
+        true
+    }
+
+    /// Speeds and delays right now, from the level tables in master mode.
+    pub fn timing(&self) -> Timing {
+        match self.mode {
+            Mode::Master => Timing::master(self.level),
+            _ => self.base
+        }
+    }
+
+    // Spawns the next piece with the inputs pressed during the entry delay.
+    // A hold press swaps it into hold right away (IHS) and rotation presses
+    // spawn it turned if it fits there (IRS), like the rotation button held
+    // through the delay in TGM. Anything else is played afterwards.
+    fn end_entry(&mut self) {
+        self.entry = Option::None;
+        let buffered = std::mem::take(&mut self.buffered);
+        self.next_piece();
+        self.checkpoint();
+        if buffered.contains(&Action::Hold) {
+            self.hold();
+        }
+        if self.game_over {
+            return
+        }
+        let turns: i32 = buffered.iter().map(|action| match action {
+            Action::RotateRight => 1,
+            Action::RotateLeft => -1,
+            Action::Rotate180 => 2,
+            _ => 0
+        }).sum();
+        // Turned where it spawned, before 20G took it down to the stack
+        let turned = self.spawned.with_rotation(self.spawned.rotation().turned(turns));
+        if turns.rem_euclid(4) != 0 && self.game_field.valid_piece(turned) {
+            self.game_piece = turned;
+            self.settle();
+        }
+        self.inputs += buffered.iter().filter(|action| initial_action(**action)).count() as u32;
+        for action in buffered.into_iter().filter(|action| !initial_action(*action)) {
+            self.perform(action);
+        }
+    }
+
+    fn next_piece(&mut self) {
+        if self.game_over {
+            return
+        }
+        match self.queue.next() {
+            Option::Some(piece) => self.spawn(piece),
+            Option::None => {
+                self.game_over = true;
+                self.events.push(Event::OutOfPieces);
+            }
+        }
+    }
+
+    /// Moves the falling piece a row down for a point, locking it if it rests
+    /// on the stack.
+    pub fn soft_drop(&mut self) {
+        if self.game_over {
+            return
+        }
+        if self.step() {
+            self.score += 1;
+        } else {
+            self.after_lock();
+        }
+    }
+
+    /// Drops the falling piece to the bottom for two points a row and locks
+    /// it.
+    pub fn hard_drop(&mut self) {
+        if self.game_over {
+            return
+        }
+        while self.step() {
+            self.score += 2;
+        }
+        self.after_lock();
+    }
+
+    /// Swaps the falling piece with the held one, or holds it and takes the
+    /// next one. Once a piece until one locks.
+    pub fn hold(&mut self) {
+        if self.game_over || self.hold_used || self.modifiers.contains(&Modifier::NoHold) {
+            return
+        }
+        if self.hold.is_none() && self.queue.is_empty() {
+            return
+        }
+        let current = self.game_piece;
+        match self.hold.take() {
+            Option::Some(held) => self.spawn(held),
+            Option::None => self.next_piece(),
+        };
+        // Held as it would spawn, not turned or moved like it was in play
+        self.hold = Option::Some(
+            Piece::new(current.piece_type(), current.color(), (0,0)).with_system(self.system)
+        );
+        self.hold_used = true;
+        self.events.push(Event::Held(current.piece_type()));
+    }
+
+    /// Performs a player input. During the entry delay inputs are kept until
+    /// the next piece spawns.
+    pub fn apply(&mut self, action: Action) {
+        if self.game_over {
+            return
+        }
+        self.events.push(Event::Input(action));
+        if self.entry.is_some() {
+            self.buffered.push(action);
+        } else {
+            self.perform(action);
+        }
+    }
+
+    fn perform(&mut self, action: Action) {
+        match action {
+            Action::MoveLeft => self.move_left(),
+            Action::MoveRight => self.move_right(),
+            Action::DasLeft => self.das_left(),
+            Action::DasRight => self.das_right(),
+            Action::SoftDrop => self.soft_drop(),
+            Action::HardDrop => self.hard_drop(),
+            Action::RotateRight => self.rotate_right(),
+            Action::RotateLeft => self.rotate_left(),
+            Action::Rotate180 => self.rotate_180(),
+            Action::Hold => self.hold(),
+        }
+        // Counted after the action, a hold press belongs to the piece it
+        // brings in
+        if finesse::is_input(action) {
+            self.count_input(action);
+        }
+        if !self.game_over && self.entry.is_none() {
+            self.settle();
+        }
+    }
+
+    // A tap and the shifts after it in the same direction, whether from
+    // key repeat or a charged DAS, count as one input once they reach the
+    // wall
+    fn count_input(&mut self, action: Action) {
+        let (action, step): (Action, fn(&Piece) -> Piece) = match action {
+            Action::MoveLeft | Action::DasLeft => (Action::MoveLeft, Piece::move_left),
+            Action::MoveRight | Action::DasRight => (Action::MoveRight, Piece::move_right),
+            _ => {
+                self.shifting = Option::None;
+                self.inputs += 1;
+                return
+            }
+        };
+        let before = match self.shifting {
+            Option::Some((direction, before)) if direction == action => before,
+            _ => self.inputs
+        };
+        self.shifting = Option::Some((action, before));
+        if self.game_field.valid_piece(step(&self.game_piece)) {
+            self.inputs += 1;
+        } else {
+            self.inputs = before + 1;
+        }
+    }
 
+    /// Display coordinates the active piece would lock at if hard dropped,
+    /// empty under the no ghost modifier.
+    pub fn ghost(&self) -> Vec<(i32,i32)> {
+        let mut ghost = match self.active() {
+            Option::Some(piece) if !self.modifiers.contains(&Modifier::NoGhost) => piece,
+            _ => return Vec::new()
+        };
+        while self.game_field.valid_piece(ghost.move_down()) {
+            ghost = ghost.move_down();
         }
+        ghost.coordinates().iter().flat_map(|cell| self.scaled(*cell)).collect()
+    }
+
+    // Display cells covered by a field cell, four of them for big pieces
+    fn scaled(&self, (x, y): (i32,i32)) -> Vec<(i32,i32)> {
+        let scale = self.scale();
+        (0 .. scale * scale)
+            .map(|i| (scale * x + i % scale, scale * y + i / scale))
+            .collect()
+    }
+
+    fn scale(&self) -> i32 {
+        if self.modifiers.contains(&Modifier::Big) { 2 } else { 1 }
     }
 
-    // Returns al element given in a (0,0) - (1,1)  space
-    pub fn elems(&self) -> Vec<((f32,f32), Color)> {
-        let piece_coords = self.game_piece.coordinates();
-        let game_piece = piece_coords
-            .iter().map(|cord|(*cord, self.game_piece.color()));
+    /// Returns all blocks in field coordinates, including the active piece
+    /// and anything in the buffer zone above the visible rows.
+    pub fn blocks(&self) -> Vec<((i32,i32), Color)> {
         let mut res = self.game_field.get_blocks();
-        res.extend(game_piece);
-        res.iter().map(|((x,y), color)| {
-            let f_x = *x as f32 * -1. / self.num_columns() as f32;
-            let f_y = *y as f32 * -1. / self.num_rows() as f32;
-            ((f_x, f_y), *color)
+        if let Option::Some(piece) = self.active() {
+            res.extend(piece.coordinates().iter().map(|cord| (*cord, piece.color())));
+        }
+        res
+    }
+
+    /// Every visible locked block and the active piece's blocks, in display
+    /// coordinates. Blocks faded out by the invisible modifier are left out.
+    pub fn visible_blocks(&self) -> Vec<((i32,i32), Block)> {
+        let field = &self.game_field;
+        let fade = self.modifiers.iter().filter_map(|modifier| match modifier {
+            Modifier::Invisible(frames) if !self.game_over => Option::Some(*frames as u64),
+            _ => Option::None
+        }).next();
+        let mut res = Vec::new();
+        for y in 0 .. field.height() {
+            for x in 0 .. field.width() {
+                if let Option::Some(block) = field.block_at(x, y) {
+                    if fade.is_none_or(|frames| self.frames - block.locked < frames) {
+                        res.extend(self.scaled((x, y)).into_iter().map(|cell| (cell, block)));
+                    }
+                }
+            }
+        }
+        if let Option::Some(active) = self.active() {
+            let piece = Block::of_piece(&active);
+            res.extend(active.coordinates().iter()
+                       .filter(|(_,y)| *y >= 0)
+                       .flat_map(|cell| self.scaled(*cell))
+                       .map(|cell| (cell, piece)));
+        }
+        res
+    }
+
+    /// Every visible block with its place as a fraction of the board, from 0
+    /// at the top left to just above -1 at the bottom right.
+    pub fn elems(&self) -> Vec<((f32,f32), Block)> {
+        self.visible_blocks().iter().map(|((x,y), block)| {
+            let f_x = -(*x as f32) / self.num_columns() as f32;
+            let f_y = -(*y as f32) / self.num_rows() as f32;
+            ((f_x, f_y), *block)
         }).collect()
     }
 
+    /// Removes and returns every event since the last call.
+    pub fn drain_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// The field with the locked blocks.
+    pub fn field(&self) -> &GameField {
+        &self.game_field
+    }
+
+    /// Seed of the piece queue, to play the game again.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The mode the game is played in.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// The skin pieces are colored with.
+    pub fn skin(&self) -> &Skin {
+        &self.skin
+    }
+
+    /// Switches skins mid game, recoloring every block and piece.
+    pub fn set_skin(&mut self, skin: Skin) {
+        let recolor = |piece: Piece| piece.with_color(skin.color_of(piece.piece_type()));
+        self.game_field.recolor(&skin);
+        self.game_piece = recolor(self.game_piece);
+        self.spawned = recolor(self.spawned);
+        self.hold = self.hold.map(recolor);
+        self.queue.recolor(&skin);
+        self.skin = skin;
+    }
+
+    /// The rotation system the tetrominoes turn by.
+    pub fn rotation_system(&self) -> &'static dyn RotationSystem {
+        self.system
+    }
+
+    /// The falling piece, or the one that just locked during the entry
+    /// delay.
+    pub fn current(&self) -> Piece {
+        self.game_piece
+    }
+
+    /// The falling piece, `None` during the entry delay.
+    pub fn active(&self) -> Option<Piece> {
+        match self.entry {
+            Option::Some(_) => Option::None,
+            Option::None => Option::Some(self.game_piece),
+        }
+    }
+
+    /// Frames left before the next piece spawns, `None` while one is falling.
+    /// Doesn't include what is left of the line clear delay.
+    pub fn entry_delay(&self) -> Option<u32> {
+        self.entry
+    }
+
+    /// Display rows of the full rows that stay on the field until the line
+    /// clear delay is over.
+    pub fn clearing_rows(&self) -> Vec<i32> {
+        self.clearing.iter()
+            .flat_map(|row| self.scaled((0, *row)))
+            .map(|(_, y)| y)
+            .collect()
+    }
+
+    /// Modifiers the game is played with.
+    pub fn modifiers(&self) -> &[Modifier] {
+        &self.modifiers
+    }
+
+    /// The upcoming pieces, shaped by the game's rotation system.
+    pub fn preview(&self) -> Vec<Piece> {
+        self.queue.preview().iter()
+            .map(|piece| piece.with_system(self.system))
+            .collect()
+    }
+
+    /// The queue pieces are dealt from.
+    pub fn queue(&self) -> &PieceQueue {
+        &self.queue
+    }
+
+    /// The held piece, in spawn orientation.
+    pub fn held(&self) -> Option<Piece> {
+        self.hold
+    }
+
+    /// How the pieces placed so far compare to the fewest inputs.
+    pub fn finesse(&self) -> &FinesseStats {
+        &self.finesse
+    }
+
+    /// Points scored.
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    /// Rows cleared.
+    pub fn lines(&self) -> u32 {
+        self.lines
+    }
+
+    /// Goes up with every piece and cleared line in master mode, elsewhere
+    /// every ten lines starting at 1.
+    pub fn level(&self) -> u32 {
+        match self.mode {
+            Mode::Master => self.level,
+            _ => self.lines / 10 + 1
+        }
+    }
+
+    /// Whether the game ended, by topping out, running out of pieces or
+    /// reaching its goal.
+    pub fn is_game_over(&self) -> bool {
+        self.game_over
+    }
+
+    /// Columns as drawn, twice the field's under the big modifier.
     pub fn num_columns(&self) -> i32 {
-        return self.game_field.width()
+        self.game_field.width() * self.scale()
     }
 
+    /// Rows as drawn, twice the field's under the big modifier.
     pub fn num_rows(&self) -> i32 {
-        return self.game_field.height()
+        self.game_field.height() * self.scale()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn master_at(level: u32) -> TetrisManager {
+        let mut manager = TetrisManager::with_config(
+            &GameConfig::default().mode(Mode::Master).seed(3)
+        );
+        manager.level = level;
+        manager
+    }
+
+    fn wait_for_spawn(manager: &mut TetrisManager) {
+        while manager.active().is_none() {
+            manager.frame();
+        }
+    }
+
+    fn locked(manager: &TetrisManager) -> Vec<(i32,i32)> {
+        manager.field().get_blocks().iter().map(|(cell,_)| *cell).collect()
+    }
+
+    fn practice(undo_depth: usize) -> TetrisManager {
+        TetrisManager::with_config(
+            &GameConfig::default().mode(Mode::Practice).seed(3).undo_depth(undo_depth)
+        )
+    }
+
+    #[test]
+    fn undo_goes_back_to_before_each_placement() {
+        let mut manager = practice(100);
+        let first = manager.current();
+        manager.apply(Action::MoveLeft);
+        manager.apply(Action::HardDrop);
+        let after_one = locked(&manager);
+        let second = manager.current();
+        manager.apply(Action::Hold);
+        manager.apply(Action::HardDrop);
+        manager.drain_events();
+
+        assert!(manager.undo());
+        assert_eq!(locked(&manager), after_one);
+        assert_eq!(manager.current(), second);
+        assert_eq!(manager.held(), Option::None);
+        assert!(manager.undo());
+        assert!(locked(&manager).is_empty());
+        assert_eq!(manager.current(), first);
+        assert!(!manager.undo());
+        assert_eq!(manager.drain_events(), vec![Event::Undone, Event::Undone]);
+    }
+
+    #[test]
+    fn redo_plays_undone_placements_again() {
+        let mut manager = practice(100);
+        manager.apply(Action::HardDrop);
+        manager.apply(Action::MoveRight);
+        manager.apply(Action::HardDrop);
+        let after_two = (locked(&manager), manager.current(), manager.queue().preview());
+        manager.undo();
+        manager.undo();
+
+        assert!(manager.redo());
+        assert!(manager.redo());
+        assert!(!manager.redo());
+        assert_eq!((locked(&manager), manager.current(), manager.queue().preview()), after_two);
+        assert!(manager.undo());
+    }
+
+    #[test]
+    fn a_new_placement_drops_the_redo_history() {
+        let mut manager = practice(100);
+        manager.apply(Action::HardDrop);
+        manager.undo();
+        manager.apply(Action::MoveLeft);
+        manager.apply(Action::HardDrop);
+        assert!(!manager.redo());
+        assert!(manager.undo());
+    }
+
+    #[test]
+    fn undo_only_keeps_the_newest_placements() {
+        let mut manager = practice(1);
+        manager.apply(Action::HardDrop);
+        manager.apply(Action::HardDrop);
+        assert!(manager.undo());
+        assert!(!manager.undo());
+
+        let mut marathon = TetrisManager::with_config(&GameConfig::default().seed(3));
+        marathon.apply(Action::HardDrop);
+        assert!(!marathon.undo());
+    }
+
+    #[test]
+    fn mirror_flips_the_field_after_every_few_pieces() {
+        let every = std::num::NonZeroU32::new(2).unwrap();
+        let mut manager = TetrisManager::with_config(
+            &GameConfig::default().seed(3).modifier(Modifier::Mirror(every))
+        );
+        let width = manager.field().width();
+        let mut cells = manager.ghost();
+        manager.apply(Action::HardDrop);
+        let mut blocks = locked(&manager);
+        cells.sort_unstable();
+        blocks.sort_unstable();
+        assert_eq!(blocks, cells);
+
+        manager.apply(Action::MoveLeft);
+        cells.extend(manager.ghost());
+        manager.apply(Action::HardDrop);
+        let mut mirrored: Vec<(i32,i32)> = cells.iter()
+            .map(|(x,y)| (width - 1 - x, *y))
+            .collect();
+        let mut blocks = locked(&manager);
+        mirrored.sort_unstable();
+        blocks.sort_unstable();
+        assert_eq!(blocks, mirrored);
+    }
+
+    #[test]
+    fn irs_at_20g_turns_the_piece_where_it_spawns_then_drops_it() {
+        let mut manager = master_at(500);
+        manager.apply(Action::HardDrop);
+        manager.apply(Action::RotateRight);
+        wait_for_spawn(&mut manager);
+
+        let piece = manager.active().unwrap();
+        assert_eq!(piece.rotation(), Rotation::Right);
+        assert!(!manager.field().valid_piece(piece.move_down()));
+    }
+
+    #[test]
+    fn irs_below_20g_leaves_the_piece_at_the_top() {
+        let mut manager = master_at(0);
+        manager.apply(Action::HardDrop);
+        manager.apply(Action::RotateLeft);
+        wait_for_spawn(&mut manager);
+
+        let piece = manager.active().unwrap();
+        assert_eq!(piece.rotation(), Rotation::Left);
+        assert!(piece.coordinates().iter().all(|(_,y)| *y < 2));
+    }
+
+    #[test]
+    fn held_pieces_are_kept_as_they_spawn() {
+        let mut manager = TetrisManager::with_config(&GameConfig::default().seed(3));
+        let current = manager.current();
+        manager.apply(Action::RotateRight);
+        manager.apply(Action::MoveLeft);
+        manager.apply(Action::Hold);
+
+        let held = manager.held().unwrap();
+        assert_eq!(held.piece_type(), current.piece_type());
+        assert_eq!((held.rotation(), held.position()), (Rotation::Up, (0,0)));
     }
 }
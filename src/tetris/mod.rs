@@ -1,4 +1,22 @@
-mod piece;
-pub mod color;
-mod gamefield;
-pub mod manager;
+pub(crate) mod piece;
+pub(crate) mod pieceset;
+pub mod rotation;
+pub(crate) mod color;
+pub(crate) mod skin;
+pub(crate) mod gamefield;
+pub(crate) mod queue;
+pub(crate) mod input;
+pub(crate) mod config;
+pub(crate) mod event;
+pub(crate) mod timing;
+pub(crate) mod modifier;
+pub(crate) mod manager;
+pub(crate) mod notation;
+pub mod fumen;
+pub(crate) mod editor;
+pub(crate) mod puzzle;
+pub(crate) mod solver;
+pub(crate) mod finesse;
+pub(crate) mod stats;
+pub(crate) mod records;
+pub(crate) mod replay;
@@ -0,0 +1,92 @@
+use std::num::NonZeroU32;
+use serde::{Deserialize, Serialize};
+
+/// A challenge that changes how a game plays. Any number of them can be
+/// combined, they are stored with results and replays so those can be told
+/// apart.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Modifier {
+    /// Locked blocks disappear this many frames after they lock, and come
+    /// back once the game is over.
+    Invisible(u32),
+    /// Pieces are twice as large on a field of half the width and height,
+    /// every cell is drawn as two by two.
+    Big,
+    /// The field flips left to right every this many pieces.
+    Mirror(NonZeroU32),
+    /// Hold is disabled.
+    NoHold,
+    /// The ghost piece isn't shown.
+    NoGhost,
+}
+
+impl Modifier {
+    /// Parses `invisible`, `big`, `mirror`, `no-hold` or `no-ghost`.
+    /// Invisible and mirror take their count after a colon, like
+    /// `mirror:5`, and default to 300 frames and 10 pieces.
+    pub fn from_name(name: &str) -> Option<Modifier> {
+        let mut parts = name.splitn(2, ':');
+        let kind = parts.next()?;
+        let count = match parts.next() {
+            Option::Some(count) => Option::Some(count.parse().ok().filter(|n| *n > 0)?),
+            Option::None => Option::None
+        };
+        match (kind, count) {
+            ("invisible", count) => Option::Some(Modifier::Invisible(count.unwrap_or(300))),
+            ("mirror", count) => NonZeroU32::new(count.unwrap_or(10)).map(Modifier::Mirror),
+            ("big", Option::None) => Option::Some(Modifier::Big),
+            ("no-hold", Option::None) => Option::Some(Modifier::NoHold),
+            ("no-ghost", Option::None) => Option::Some(Modifier::NoGhost),
+            _ => Option::None
+        }
+    }
+
+    /// Parses a comma separated list like `big,invisible:120`.
+    pub fn parse_list(names: &str) -> Option<Vec<Modifier>> {
+        names.split(',')
+            .map(|name| Modifier::from_name(name.trim()))
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Modifier {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            Modifier::Invisible(frames) => write!(fmt, "invisible:{}", frames),
+            Modifier::Big => write!(fmt, "big"),
+            Modifier::Mirror(pieces) => write!(fmt, "mirror:{}", pieces),
+            Modifier::NoHold => write!(fmt, "no-hold"),
+            Modifier::NoGhost => write!(fmt, "no-ghost"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_survive_a_round_trip() {
+        let modifiers = Modifier::parse_list("big, invisible:120,mirror,no-hold").unwrap();
+        assert_eq!(modifiers, vec![
+            Modifier::Big,
+            Modifier::Invisible(120),
+            Modifier::Mirror(NonZeroU32::new(10).unwrap()),
+            Modifier::NoHold,
+        ]);
+        for modifier in modifiers {
+            assert_eq!(Modifier::from_name(&modifier.to_string()), Option::Some(modifier));
+        }
+    }
+
+    #[test]
+    fn zero_and_unknown_counts_are_rejected() {
+        assert_eq!(Modifier::from_name("mirror:0"), Option::None);
+        assert_eq!(Modifier::from_name("invisible:0"), Option::None);
+        assert_eq!(Modifier::from_name("big:2"), Option::None);
+        assert_eq!(Modifier::parse_list("big,upside-down"), Option::None);
+        assert!(serde_json::from_str::<Modifier>("{\"mirror\":0}").is_err());
+        assert!(serde_json::from_str::<Modifier>("{\"mirror\":3}").is_ok());
+    }
+}
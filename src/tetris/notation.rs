@@ -0,0 +1,299 @@
+use std::fmt;
+use std::str::FromStr;
+use crate::tetris::color::Color;
+use crate::tetris::gamefield::{Block, BlockKind, GameField};
+use crate::tetris::piece::{Piece, PieceType};
+use crate::tetris::skin::Skin;
+
+/// A board position as written in the text notation: the field, an optional
+/// active piece and the hold and queue.
+///
+/// The notation is one line per row, top row first. `.` is an empty cell,
+/// `#` garbage and the piece letters `IJLOSTZ` are locked blocks. The active
+/// piece is written with lowercase letters. Optional header lines before the
+/// rows give the hold, the queue and how many of the rows are buffer rows:
+///
+/// ```text
+/// hold: T
+/// queue: IOSZ
+/// buffer: 1
+/// ..........
+/// ..........
+/// ....ttt...
+/// .....t....
+/// IIII.....O
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Setup {
+    /// The locked blocks, with the buffer rows the header asked for.
+    pub field: GameField,
+    /// The piece written in lowercase letters, if any.
+    pub active: Option<Piece>,
+    /// The held piece.
+    pub hold: Option<PieceType>,
+    /// The pieces still to come, next first.
+    pub queue: Vec<PieceType>,
+}
+
+/// Why a board couldn't be read or written.
+#[derive(Debug, PartialEq, Eq)]
+pub enum NotationError {
+    /// There are no rows.
+    EmptyField,
+    /// The row with this index has a different width than the first.
+    UnevenRows(usize),
+    /// A cell is none of `.`, `#` or a piece letter.
+    UnknownCell(char),
+    /// A piece letter in the hold or queue isn't one of `IJLOSTZ`.
+    UnknownPiece(char),
+    /// A header line is none of `hold:`, `queue:` or `buffer:`.
+    UnknownHeader(String),
+    /// The lowercase cells aren't a tetromino in any orientation.
+    InvalidActive,
+    /// The `buffer:` header isn't a row count below the number of rows.
+    InvalidBuffer(String),
+    /// Fumen data that's malformed or that fumen can't carry.
+    UnsupportedFumen(String),
+    /// The field is larger than the one it has to fit in.
+    FieldTooLarge,
+}
+
+impl std::error::Error for NotationError {}
+
+impl fmt::Display for NotationError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            NotationError::EmptyField => write!(fmt, "board has no rows"),
+            NotationError::UnevenRows(row) => {
+                write!(fmt, "row {} has a different width", row)
+            },
+            NotationError::UnknownCell(c) => write!(fmt, "unknown cell '{}'", c),
+            NotationError::UnknownPiece(c) => write!(fmt, "unknown piece '{}'", c),
+            NotationError::UnknownHeader(h) => write!(fmt, "unknown header '{}'", h),
+            NotationError::InvalidActive => {
+                write!(fmt, "active piece cells don't form a piece")
+            },
+            NotationError::InvalidBuffer(b) => {
+                write!(fmt, "buffer '{}' isn't a row count below the number of rows", b)
+            },
+            NotationError::UnsupportedFumen(why) => {
+                write!(fmt, "unsupported fumen data: {}", why)
+            },
+            NotationError::FieldTooLarge => {
+                write!(fmt, "field doesn't fit the target size")
+            },
+        }
+    }
+}
+
+// Colors for blocks that only have a piece letter to go on
+pub(crate) fn letter_color(piece_type: PieceType) -> Color {
+    Skin::guideline().color_of(piece_type)
+}
+
+fn parse_pieces(text: &str) -> Result<Vec<PieceType>, NotationError> {
+    text.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| PieceType::from_letter(c).ok_or(NotationError::UnknownPiece(c)))
+        .collect()
+}
+
+fn block_letter(block: &Block) -> char {
+    match block.kind {
+        BlockKind::Piece(piece_type) => piece_type.letter(),
+        BlockKind::Garbage => '#',
+    }
+}
+
+impl Setup {
+    /// `field` with no active piece, hold or queue.
+    pub fn new(field: GameField) -> Setup {
+        Setup {
+            field,
+            active: Option::None,
+            hold: Option::None,
+            queue: Vec::new(),
+        }
+    }
+}
+
+impl FromStr for Setup {
+    type Err = NotationError;
+
+    fn from_str(text: &str) -> Result<Setup, NotationError> {
+        let mut hold = Option::None;
+        let mut queue = Vec::new();
+        let mut buffer = "0";
+        let mut rows: Vec<&str> = Vec::new();
+        for line in text.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+            match line.find(':') {
+                Option::Some(split) => {
+                    let value = &line[split + 1 ..];
+                    match line[.. split].trim() {
+                        "hold" => hold = parse_pieces(value)?.first().cloned(),
+                        "queue" => queue = parse_pieces(value)?,
+                        "buffer" => buffer = value.trim(),
+                        header => {
+                            return Result::Err(
+                                NotationError::UnknownHeader(header.to_string())
+                            )
+                        }
+                    }
+                },
+                Option::None => rows.push(line)
+            }
+        }
+
+        let width = rows.first().ok_or(NotationError::EmptyField)?.chars().count();
+        let buffer = buffer.parse::<usize>().ok()
+            .filter(|b| *b < rows.len())
+            .ok_or_else(|| NotationError::InvalidBuffer(buffer.to_string()))?;
+        let mut field = GameField::with_buffer(
+            width as i32,
+            (rows.len() - buffer) as i32,
+            buffer as i32
+        );
+        let mut active_type = Option::None;
+        let mut active_cells = Vec::new();
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() != width {
+                return Result::Err(NotationError::UnevenRows(y))
+            }
+            for (x, c) in row.chars().enumerate() {
+                let (x, y) = (x as i32, y as i32 - buffer as i32);
+                match c {
+                    '.' => (),
+                    '#' => field.set_block(x, y, Block::garbage()),
+                    c => {
+                        let piece_type = PieceType::from_letter(c)
+                            .ok_or(NotationError::UnknownCell(c))?;
+                        if c.is_lowercase() {
+                            if active_type.is_some_and(|t| t != piece_type) {
+                                return Result::Err(NotationError::InvalidActive)
+                            }
+                            active_type = Option::Some(piece_type);
+                            active_cells.push((x, y));
+                        } else {
+                            field.set_block(x, y, Block {
+                                kind: BlockKind::Piece(piece_type),
+                                color: letter_color(piece_type),
+                                locked: 0,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        let active = match active_type {
+            Option::Some(piece_type) => Option::Some(
+                Piece::from_coordinates(
+                    piece_type,
+                    letter_color(piece_type),
+                    &active_cells
+                ).ok_or(NotationError::InvalidActive)?
+            ),
+            Option::None => Option::None
+        };
+
+        Result::Ok(Setup {
+            field,
+            active,
+            hold,
+            queue,
+        })
+    }
+}
+
+impl fmt::Display for Setup {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        if let Option::Some(hold) = self.hold {
+            writeln!(fmt, "hold: {}", hold.letter())?;
+        }
+        if !self.queue.is_empty() {
+            let queue: String = self.queue.iter().map(|p| p.letter()).collect();
+            writeln!(fmt, "queue: {}", queue)?;
+        }
+        if self.field.buffer() > 0 {
+            writeln!(fmt, "buffer: {}", self.field.buffer())?;
+        }
+        let active: Vec<(i32,i32)> = self.active.iter()
+            .flat_map(|p| p.coordinates().to_vec())
+            .collect();
+        let active_letter = self.active
+            .map_or('.', |p| p.piece_type().letter().to_ascii_lowercase());
+        for y in -self.field.buffer() .. self.field.height() {
+            for x in 0 .. self.field.width() {
+                let c = match self.field.block_at(x, y) {
+                    _ if active.contains(&(x, y)) => active_letter,
+                    Option::Some(block) => block_letter(&block),
+                    Option::None => '.',
+                };
+                write!(fmt, "{}", c)?;
+            }
+            writeln!(fmt)?;
+        }
+        Result::Ok(())
+    }
+}
+
+impl FromStr for GameField {
+    type Err = NotationError;
+
+    fn from_str(text: &str) -> Result<GameField, NotationError> {
+        text.parse::<Setup>().map(|setup| setup.field)
+    }
+}
+
+impl fmt::Display for GameField {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        Setup::new(self.clone()).fmt(fmt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setups_survive_a_round_trip() {
+        let text = "hold: T\nqueue: IOSZ\n....\n.lll\n.l..\n#IZZ\n";
+        let setup: Setup = text.parse().unwrap();
+        assert_eq!(setup.hold, Option::Some(PieceType::T));
+        assert_eq!(setup.queue, vec![PieceType::I, PieceType::O, PieceType::S, PieceType::Z]);
+        assert_eq!(setup.active.map(|p| p.piece_type()), Option::Some(PieceType::L));
+        assert_eq!(setup.field.block_at(0, 3), Option::Some(Block::garbage()));
+        assert_eq!(setup.to_string(), text);
+        assert_eq!(setup.to_string().parse::<Setup>(), Result::Ok(setup));
+    }
+
+    #[test]
+    fn buffer_rows_survive_a_round_trip() {
+        let text = "buffer: 2\n.t..\nttt.\n....\nO...\n";
+        let setup: Setup = text.parse().unwrap();
+        assert_eq!((setup.field.height(), setup.field.buffer()), (2, 2));
+        assert_eq!(
+            setup.active.unwrap().coordinates().iter().map(|(_,y)| *y).min(),
+            Option::Some(-2)
+        );
+        assert!(setup.field.block_at(0, 1).is_some());
+        assert_eq!(setup.to_string(), text);
+
+        let field: GameField = "buffer: 1\n#...\n....".parse().unwrap();
+        assert!(field.block_at(0, -1).is_some());
+        assert_eq!(field.to_string().parse::<GameField>(), Result::Ok(field));
+    }
+
+    #[test]
+    fn bad_notation_is_rejected() {
+        let parse = |text: &str| text.parse::<Setup>().unwrap_err();
+        assert_eq!(parse("hold: T"), NotationError::EmptyField);
+        assert_eq!(parse("....\n..."), NotationError::UnevenRows(1));
+        assert_eq!(parse("..x."), NotationError::UnknownCell('x'));
+        assert_eq!(parse("queue: IX\n...."), NotationError::UnknownPiece('X'));
+        assert_eq!(parse("next: I\n...."), NotationError::UnknownHeader(String::from("next")));
+        assert_eq!(parse("....\n.t..\n..t."), NotationError::InvalidActive);
+        assert_eq!(parse("buffer: 2\n....\n...."), NotationError::InvalidBuffer(String::from("2")));
+        assert_eq!(parse("buffer: x\n...."), NotationError::InvalidBuffer(String::from("x")));
+    }
+}
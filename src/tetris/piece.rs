@@ -1,168 +1,252 @@
 use crate::tetris::color::Color;
+use crate::tetris::pieceset::{tetromino_defs, PieceDef};
+use crate::tetris::rotation::{RotationSystem, Srs};
+use crate::tetris::skin::Skin;
 
 
+/// The seven tetrominoes, named after the letter they resemble, or a piece
+/// from a `PieceSet` loaded from data.
 // Named according to wikipedia, couldn't figure out good names for all of them
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub enum PieceType {
-    I = 0,
+    /// The straight piece.
+    I,
+    /// Three in a row with the left end raised.
     J,
+    /// Three in a row with the right end raised.
     L,
+    /// The square.
     O,
+    /// The skew piece rising to the right.
     S,
+    /// Three in a row with the middle raised.
     T,
+    /// The skew piece rising to the left.
     Z,
+    /// A piece of a loaded set.
+    Custom(&'static PieceDef),
 }
 
-const PIECE_TYPES : [PieceType;7] =
+/// The seven tetrominoes in the order of their letters.
+pub const PIECE_TYPES : [PieceType;7] =
     [PieceType::I,PieceType::J,PieceType::L,
      PieceType::O,PieceType::S,PieceType::T,
      PieceType::Z];
 
+impl std::fmt::Debug for PieceType {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            PieceType::Custom(def) => write!(fmt, "Custom({})", def.letter),
+            _ => write!(fmt, "{}", self.letter()),
+        }
+    }
+}
+
 impl PieceType {
+    /// One of the seven tetrominoes by its letter.
+    pub fn from_letter(letter: char) -> Option<PieceType> {
+        match letter.to_ascii_uppercase() {
+            'I' => Option::Some(PieceType::I),
+            'J' => Option::Some(PieceType::J),
+            'L' => Option::Some(PieceType::L),
+            'O' => Option::Some(PieceType::O),
+            'S' => Option::Some(PieceType::S),
+            'T' => Option::Some(PieceType::T),
+            'Z' => Option::Some(PieceType::Z),
+            _ => Option::None
+        }
+    }
+
+    /// The letter the piece is named by.
+    pub fn letter(self) -> char {
+        match self {
+            PieceType::I => 'I',
+            PieceType::J => 'J',
+            PieceType::L => 'L',
+            PieceType::O => 'O',
+            PieceType::S => 'S',
+            PieceType::T => 'T',
+            PieceType::Z => 'Z',
+            PieceType::Custom(def) => def.letter,
+        }
+    }
+
+    /// Shape, spawn offset and kicks of the piece, under SRS for the seven
+    /// tetrominoes.
+    pub fn def(self) -> &'static PieceDef {
+        match self {
+            PieceType::Custom(def) => def,
+            standard => {
+                let i = PIECE_TYPES.iter().position(|p| *p == standard).unwrap();
+                &tetromino_defs()[i]
+            }
+        }
+    }
+
+    /// Number of blocks in the piece.
+    pub fn size(self) -> usize {
+        self.def().size()
+    }
+
+    /// Whether this is one of the seven standard pieces.
+    pub fn is_tetromino(self) -> bool {
+        !matches!(self, PieceType::Custom(_))
+    }
+
+    /// One of the seven tetrominoes picked by `rng`.
     pub fn from_rng<R: rand::Rng>(rng: &mut R) -> PieceType {
         let n: usize = rng.gen();
-        let t = PIECE_TYPES[n % PIECE_TYPES.len()];
-        t
+        PIECE_TYPES[n % PIECE_TYPES.len()]
     }
 }
 
-const I_LAYOUT : [[(i32,i32);4];2] =
-    [
-        [(0,0),(1,0),(2,0),(3,0)],
-        [(2,-1),(2,0),(2,1),(2,2)],
-    ];
-
-const J_LAYOUT : [[(i32,i32);4];4] =
-    [
-        [(-1,-1),(-1,0),(0,0),(1,0)],
-        [(1,-1),(0,-1),(0,0),(0,1)],
-        [(-1,0),(0,0),(1,0),(1,1)],
-        [(-1,1),(0,-1),(0,0),(0,1)]
-    ];
-
-const L_LAYOUT : [[(i32,i32);4];4] =
-    [
-        [(1,-1),(-1,0),(0,0),(1,0)],
-        [(1,1),(0,-1),(0,0),(0,1)],
-        [(-1,0),(0,0),(1,0),(-1,1)],
-        [(-1,-1),(0,-1),(0,0),(0,1)]
-    ];
-
-const O_LAYOUT : [[(i32,i32);4]; 1] =
-    [[(0,0), (1,0), (0,1), (1,1)]];
-
-const S_LAYOUT : [[(i32,i32);4];2] =
-    [
-        [(-1,0),(0,0),(0,-1),(1,-1)],
-        [(0,-1),(0,0), (1,0),(1,1)],
-    ];
-const T_LAYOUT : [[(i32,i32);4];4] =
-    [
-        [(0,-1),(-1,0),(0,0),(1,0)],
-        [(1,0),(0,-1),(0,0),(0,1)],
-        [(-1,0),(0,0),(1,0),(0,1)],
-        [(-1,0),(0,1),(0,0),(0,-1)]
-    ];
-const Z_LAYOUT : [[(i32,i32);4];2] =
-    [
-        [(-1,-1),(0,-1),(0,0),(1,0)],
-        [(1,-1),(1,0),(0,0),(0,1)],
-    ];
-
-fn get_base_coordinates(
-    piece_type: PieceType,
-    rotation: Rotation
-) -> [(i32,i32); 4] {
-    let rot = rotation as usize;
-    let res = match piece_type {
-        PieceType::I => { I_LAYOUT.as_ref()},
-        PieceType::J => { J_LAYOUT.as_ref()},
-        PieceType::L => { L_LAYOUT.as_ref()},
-        PieceType::O => { O_LAYOUT.as_ref()},
-        PieceType::S => { S_LAYOUT.as_ref()},
-        PieceType::T => { T_LAYOUT.as_ref()},
-        PieceType::Z => { Z_LAYOUT.as_ref()},
-    };
-
-    res[rot % res.len()]
+/// Orientation of a piece, `Up` is the spawn orientation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Default)]
+pub enum Rotation {
+    /// As the piece spawns.
+    #[default]
+    Up = 0,
+    /// A quarter turn clockwise from `Up`.
+    Right,
+    /// Upside down.
+    Down,
+    /// A quarter turn counterclockwise from `Up`.
+    Left
 }
 
+const ROTATIONS : [Rotation; 4] =
+    [Rotation::Up, Rotation::Right, Rotation::Down, Rotation::Left];
 
-#[derive(Copy, Clone, Debug)]
-pub enum Rotation {
-    Up = 0, Right , Down, Left
-}
 
-impl Default for Rotation {
-    fn default() -> Rotation {
-        Rotation::Up
+impl Rotation {
+    /// The orientation after `quarter_turns` clockwise quarter turns from
+    /// `Up`. Negative turns go counterclockwise, any number wraps around.
+    pub fn from_turns(quarter_turns: i32) -> Rotation {
+        ROTATIONS[quarter_turns.rem_euclid(4) as usize]
     }
-}
 
-impl Rotation {
+    /// Clockwise quarter turns from `Up`, 0 to 3.
+    pub fn turns(self) -> i32 {
+        match self {
+            Rotation::Up => 0,
+            Rotation::Right => 1,
+            Rotation::Down => 2,
+            Rotation::Left => 3,
+        }
+    }
+
+    /// Turned by `quarter_turns` clockwise, or counterclockwise if negative.
+    pub fn turned(self, quarter_turns: i32) -> Rotation {
+        Rotation::from_turns(self.turns() + quarter_turns)
+    }
+
+    /// The orientation after a quarter turn counterclockwise.
     pub fn rotate_left(self) -> Rotation {
-        let n = ((((self as i8) - 1) % 4) + 4) % 4;
-        println!("ASD {}", n);
-        unsafe { std::mem::transmute(n)}
+        self.turned(-1)
     }
 
+    /// The orientation after a quarter turn clockwise.
     pub fn rotate_right(self) -> Rotation {
-        let n = ((((self as i8) + 1) % 4) + 4) % 4;
-        unsafe { std::mem::transmute(n)}
+        self.turned(1)
     }
 
+    /// The orientation after a half turn.
+    pub fn rotate_180(self) -> Rotation {
+        self.turned(2)
+    }
 }
 
 
 
-#[derive(Copy, Clone, Debug)]
+/// A piece with a color, orientation and position on the field, shaped by
+/// its rotation system.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Piece {
     color: Color,
     piece_type: PieceType,
     rotation: Rotation,
-    position: (i32, i32)
+    position: (i32, i32),
+    system: &'static dyn RotationSystem,
 }
 
 impl Piece {
 
+    /// A tetromino picked by `rng` in the default skin's color, at the origin.
     pub fn from_rng<R: rand::Rng>(mut rng: &mut R) -> Piece {
         let p = PieceType::from_rng(&mut rng);
-        let c = Color::from_rng(&mut rng);
         Piece::new(
             p,
-            c,
+            Skin::default().color_of(p),
             (0,0)
         )
     }
 
+    /// A piece in spawn orientation at `position`, turning by SRS.
     pub fn new(
         piece_type: PieceType,
         color: Color,
         position: (i32, i32)
     ) -> Piece {
         Piece {
-            color: color,
-            piece_type: piece_type,
+            color,
+            piece_type,
             rotation: Rotation::Up,
-            position: position
+            position,
+            system: &Srs,
+        }
+    }
+
+    /// Finds the orientation and position that cover exactly `cells`, if the
+    /// cells form a `piece_type` piece at all.
+    pub fn from_coordinates(
+        piece_type: PieceType,
+        color: Color,
+        cells: &[(i32,i32)]
+    ) -> Option<Piece> {
+        if cells.is_empty() || cells.len() != piece_type.size() {
+            return Option::None
+        }
+        let mut wanted = cells.to_vec();
+        wanted.sort_unstable();
+        for rotation in ROTATIONS.iter() {
+            let mut base = piece_type.def().cells(*rotation).to_vec();
+            base.sort_unstable();
+            let (dx, dy) = (wanted[0].0 - base[0].0, wanted[0].1 - base[0].1);
+            let fits = base.iter().zip(wanted.iter())
+                .all(|((bx,by),(wx,wy))| bx + dx == *wx && by + dy == *wy);
+            if fits {
+                return Option::Some(Piece {
+                    color,
+                    piece_type,
+                    rotation: *rotation,
+                    position: (dx, dy),
+                    system: &Srs,
+                })
+            }
         }
+        Option::None
     }
 
+    /// Moves the piece to `coords`.
     pub fn set_position(&mut self, coords: (i32, i32)) {
         self.position = coords
     }
 
-    pub fn coordinates(&self) -> [(i32,i32);4]{
-        let mut coords = get_base_coordinates(self.piece_type, self.rotation);
+    /// Shape, spawn offset and kicks of the piece in its rotation system.
+    pub fn def(&self) -> &'static PieceDef {
+        self.system.def(self.piece_type)
+    }
+
+    /// The field cells covered by the piece.
+    pub fn coordinates(&self) -> Vec<(i32,i32)> {
         let (pos_x, pos_y) = self.position;
-        for i in 0 .. coords.len() {
-            let (x,y) = coords[i];
-            let translated = (x + pos_x, y + pos_y);
-            coords[i] = translated
-        };
-        coords
+        self.def().cells(self.rotation).iter()
+            .map(|(x,y)| (x + pos_x, y + pos_y))
+            .collect()
     }
 
+    /// The piece turned a quarter clockwise, without checking the field.
     pub fn rotate_right(self) -> Piece {
         Piece {
             rotation: self.rotation.rotate_right(),
@@ -170,6 +254,7 @@ impl Piece {
         }
     }
 
+    /// The piece turned a quarter counterclockwise, without checking the field.
     pub fn rotate_left(self) -> Piece {
         Piece {
             rotation: self.rotation.rotate_left(),
@@ -177,10 +262,64 @@ impl Piece {
         }
     }
 
+    /// The piece turned around, without checking the field.
+    pub fn rotate_180(self) -> Piece {
+        Piece {
+            rotation: self.rotation.rotate_180(),
+            .. self
+        }
+    }
+
+    /// The piece in `rotation`, without checking the field.
+    pub fn with_rotation(self, rotation: Rotation) -> Piece {
+        Piece {
+            rotation,
+            .. self
+        }
+    }
+
+    /// The same piece turning by `system`, its position and state are kept.
+    pub fn with_system(self, system: &'static dyn RotationSystem) -> Piece {
+        Piece {
+            system,
+            .. self
+        }
+    }
+
+    /// The same piece in `color`.
+    pub fn with_color(self, color: Color) -> Piece {
+        Piece {
+            color,
+            .. self
+        }
+    }
+
+    /// The color the piece is drawn in.
     pub fn color(&self) -> Color {
         self.color
     }
 
+    /// Which piece this is.
+    pub fn piece_type(&self) -> PieceType {
+        self.piece_type
+    }
+
+    /// The orientation of the piece.
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    /// The rotation system the piece turns by.
+    pub fn system(&self) -> &'static dyn RotationSystem {
+        self.system
+    }
+
+    /// Position of the piece's origin on the field.
+    pub fn position(&self) -> (i32, i32) {
+        self.position
+    }
+
+    /// The piece a row further down.
     pub fn move_down(&self) -> Piece {
         let (x,y) = self.position;
         Piece{
@@ -189,6 +328,16 @@ impl Piece {
         }
     }
 
+    /// The piece moved `dx` columns right and `dy` rows down.
+    pub fn moved_by(&self, dx: i32, dy: i32) -> Piece {
+        let (x,y) = self.position;
+        Piece{
+            position: (x + dx, y + dy),
+            .. *self
+        }
+    }
+
+    /// The piece a column further left.
     pub fn move_left(&self) -> Piece {
         let (x,y) = self.position;
         Piece{
@@ -197,6 +346,7 @@ impl Piece {
         }
     }
 
+    /// The piece a column further right.
     pub fn move_right(&self) -> Piece {
         let (x,y) = self.position;
         Piece{
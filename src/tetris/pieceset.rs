@@ -0,0 +1,463 @@
+use std::fs;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use crate::tetris::color::Color;
+use crate::tetris::piece::{PieceType, Rotation, PIECE_TYPES};
+
+/// Why a piece set couldn't be loaded.
+#[derive(Debug)]
+pub enum PieceSetError {
+    /// The file couldn't be read.
+    IOError(std::io::Error),
+    /// A line that can't be read, with its line number.
+    InvalidLine(usize),
+    /// A rotation state without exactly one `@` or `+` origin, with the
+    /// line number where it starts.
+    MissingOrigin(usize),
+    /// A rotation state without any blocks, with the line number where it
+    /// starts.
+    EmptyState(usize),
+    /// Settings or cells before the first `piece:` line.
+    NoPiece(usize),
+    /// A piece with rotation states of different sizes.
+    UnevenStates(char),
+    /// A set without any pieces.
+    EmptySet,
+}
+
+impl std::error::Error for PieceSetError {}
+
+impl std::fmt::Display for PieceSetError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            PieceSetError::IOError(e) => write!(fmt, "{}", e),
+            PieceSetError::InvalidLine(line) => write!(fmt, "can't read line {}", line),
+            PieceSetError::MissingOrigin(line) => {
+                write!(fmt, "the shape at line {} needs exactly one origin", line)
+            },
+            PieceSetError::EmptyState(line) => {
+                write!(fmt, "the shape at line {} has no blocks", line)
+            },
+            PieceSetError::NoPiece(line) => {
+                write!(fmt, "line {} comes before the first piece", line)
+            },
+            PieceSetError::UnevenStates(piece) => {
+                write!(fmt, "rotation states of {} have different sizes", piece)
+            },
+            PieceSetError::EmptySet => write!(fmt, "set has no pieces"),
+        }
+    }
+}
+
+// Kicks for turning from one rotation to another
+type Transition = ((Rotation, Rotation), Vec<(i32,i32)>);
+
+/// Offsets tried in order when a rotation is blocked, in field coordinates.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct KickTable {
+    default: Vec<(i32,i32)>,
+    transitions: Vec<Transition>,
+}
+
+impl KickTable {
+    /// Offsets for turning from `from` to `to`. Without any kicks only the
+    /// plain rotation is tried.
+    pub fn kicks(&self, from: Rotation, to: Rotation) -> &[(i32,i32)] {
+        // Later entries win, so a piece can override kicks set for the set
+        let listed = self.transitions.iter().rev()
+            .find(|(transition, _)| *transition == (from, to))
+            .map(|(_, kicks)| kicks.as_slice());
+        match listed {
+            Option::Some(kicks) => kicks,
+            Option::None if !self.default.is_empty() => &self.default,
+            Option::None => &[(0,0)],
+        }
+    }
+}
+
+/// Shape and settings of one piece type.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PieceDef {
+    /// The letter the piece is known by in boards, queues and skins.
+    pub letter: char,
+    /// Color used when a skin has none for the letter.
+    pub color: Color,
+    /// Cells of every rotation state relative to the piece position, the
+    /// piece cycles through them when rotated.
+    pub states: Vec<Vec<(i32,i32)>>,
+    /// Moves the spawn position away from the field's center column.
+    pub spawn: (i32,i32),
+    /// Offsets tried when a turn is blocked.
+    pub kicks: KickTable,
+}
+
+impl PieceDef {
+    /// Cells of the piece in `rotation`, wrapping around for pieces with
+    /// fewer than four states.
+    pub fn cells(&self, rotation: Rotation) -> &[(i32,i32)] {
+        &self.states[rotation.turns() as usize % self.states.len()]
+    }
+
+    /// Number of blocks in the piece.
+    pub fn size(&self) -> usize {
+        self.states[0].len()
+    }
+}
+
+/// The pieces a game deals from.
+///
+/// Sets are written one piece after another. Each piece starts with a
+/// `piece:` line giving its letter, followed by optional `color: #rrggbb`,
+/// `spawn: x,y` and `kicks:` lines and then its rotation states as grids
+/// separated by blank lines. `#` is a block, `.` empty, and the origin the
+/// piece turns around is `@`, or `+` if it's empty. A piece written with one
+/// state gets the others by turning it around the origin, up to the first
+/// turn that gives back its shape. `kicks: 0,0 -1,0` lists the offsets tried
+/// for every turn, `kicks 0>1:` for the turn from state 0 to 1 only, and
+/// wins over `kicks:`. A `kicks` line or `name:` before the first piece
+/// applies to the whole set, a piece's own `kicks 0>1:` wins over the set's.
+/// Rows go down and columns right, as on the field. A set always has at
+/// least one piece.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PieceSet {
+    /// The name given with `name:`, empty if there is none.
+    pub name: String,
+    pieces: Vec<PieceType>,
+}
+
+static TETROMINOES: OnceLock<Vec<PieceDef>> = OnceLock::new();
+static PENTOMINOES: OnceLock<PieceSet> = OnceLock::new();
+static TROMINOES: OnceLock<PieceSet> = OnceLock::new();
+// Every custom piece parsed so far. `PieceType` is `Copy` and refers to its
+// definition for the rest of the program, so parsing the same piece again,
+// like when a set is reloaded for a new game, hands out the stored one
+static CUSTOM_DEFS: Mutex<Vec<&'static PieceDef>> = Mutex::new(Vec::new());
+
+/// Shapes of the seven standard pieces, in the order of `PIECE_TYPES`.
+pub(crate) fn tetromino_defs() -> &'static [PieceDef] {
+    TETROMINOES.get_or_init(|| {
+        parse_defs(include_str!("../../pieces/tetrominoes.txt"))
+            .expect("built in tetromino set is valid").1
+    })
+}
+
+fn parse_offsets(text: &str, line: usize) -> Result<Vec<(i32,i32)>, PieceSetError> {
+    text.split_whitespace().map(|pair| {
+        let mut parts = pair.split(',').map(|n| n.trim().parse::<i32>());
+        match (parts.next(), parts.next(), parts.next()) {
+            (Option::Some(Result::Ok(x)), Option::Some(Result::Ok(y)), Option::None) => {
+                Result::Ok((x, y))
+            },
+            _ => Result::Err(PieceSetError::InvalidLine(line))
+        }
+    }).collect()
+}
+
+fn parse_rotation(text: &str) -> Option<Rotation> {
+    match text.trim().parse::<i32>() {
+        Result::Ok(n) if (0 .. 4).contains(&n) => Option::Some(Rotation::from_turns(n)),
+        _ => Option::None
+    }
+}
+
+fn parse_grid(rows: &[&str], line: usize) -> Result<Vec<(i32,i32)>, PieceSetError> {
+    let mut origin = Option::None;
+    let mut cells = Vec::new();
+    for (y, row) in rows.iter().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            let (x, y) = (x as i32, y as i32);
+            match c {
+                '#' => cells.push((x, y)),
+                '@' | '+' if origin.is_some() => {
+                    return Result::Err(PieceSetError::MissingOrigin(line))
+                },
+                '@' => {
+                    origin = Option::Some((x, y));
+                    cells.push((x, y));
+                },
+                '+' => origin = Option::Some((x, y)),
+                '.' => (),
+                _ => return Result::Err(PieceSetError::InvalidLine(line + y as usize))
+            }
+        }
+    }
+    let (ox, oy) = origin.ok_or(PieceSetError::MissingOrigin(line))?;
+    if cells.is_empty() {
+        return Result::Err(PieceSetError::EmptyState(line))
+    }
+    Result::Ok(cells.iter().map(|(x,y)| (x - ox, y - oy)).collect())
+}
+
+// Turns a state a quarter clockwise until it has the starting shape again,
+// wherever that is. Like the O, a piece whose turns only move it around
+// doesn't turn at all
+fn generate_states(first: Vec<(i32,i32)>) -> Vec<Vec<(i32,i32)>> {
+    let sorted = |cells: &[(i32,i32)]| {
+        let mut cells = cells.to_vec();
+        cells.sort_unstable();
+        let (x0, y0) = cells[0];
+        cells.iter().map(|(x,y)| (x - x0, y - y0)).collect::<Vec<_>>()
+    };
+    let start = sorted(&first);
+    let mut states = vec![first];
+    while states.len() < 4 {
+        let turned: Vec<(i32,i32)> = states[states.len() - 1].iter()
+            .map(|(x,y)| (-y, *x))
+            .collect();
+        if sorted(&turned) == start {
+            break
+        }
+        states.push(turned);
+    }
+    states
+}
+
+struct DefBuilder {
+    letter: char,
+    color: Color,
+    spawn: (i32,i32),
+    kicks: KickTable,
+    states: Vec<Vec<(i32,i32)>>,
+}
+
+impl DefBuilder {
+    fn build(self) -> Result<PieceDef, PieceSetError> {
+        let mut states = self.states;
+        if states.len() == 1 {
+            states = generate_states(states.remove(0));
+        }
+        if states.is_empty() || states.iter().any(|s| s.len() != states[0].len()) {
+            return Result::Err(PieceSetError::UnevenStates(self.letter))
+        }
+        Result::Ok(PieceDef {
+            letter: self.letter,
+            color: self.color,
+            states,
+            spawn: self.spawn,
+            kicks: self.kicks,
+        })
+    }
+}
+
+pub(crate) fn parse_defs(text: &str) -> Result<(String, Vec<PieceDef>), PieceSetError> {
+    let mut name = String::new();
+    let mut set_kicks = KickTable::default();
+    let mut defs = Vec::new();
+    let mut current: Option<DefBuilder> = Option::None;
+    let mut grid: Vec<&str> = Vec::new();
+    let mut grid_line = 0;
+
+    let lines: Vec<&str> = text.lines().chain(std::iter::once("")).collect();
+    for (i, line) in lines.iter().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+        let is_grid = !line.is_empty()
+            && line.chars().all(|c| "#.@+".contains(c));
+        if is_grid {
+            if grid.is_empty() {
+                grid_line = line_number;
+            }
+            grid.push(line);
+            continue
+        }
+        if !grid.is_empty() {
+            let state = parse_grid(&grid, grid_line)?;
+            current.as_mut().ok_or(PieceSetError::NoPiece(grid_line))?
+                .states.push(state);
+            grid.clear();
+        }
+        if line.is_empty() {
+            continue
+        }
+
+        let split = line.find(':').ok_or(PieceSetError::InvalidLine(line_number))?;
+        let (key, value) = (line[.. split].trim(), line[split + 1 ..].trim());
+        if key == "piece" {
+            if let Option::Some(done) = current.take() {
+                defs.push(done.build()?);
+            }
+            let mut letters = value.chars();
+            let letter = match (letters.next(), letters.next()) {
+                (Option::Some(c), Option::None) => c,
+                _ => return Result::Err(PieceSetError::InvalidLine(line_number))
+            };
+            current = Option::Some(DefBuilder {
+                letter,
+                color: Color::GRAY,
+                spawn: (0, 0),
+                kicks: set_kicks.clone(),
+                states: Vec::new(),
+            });
+            continue
+        }
+
+        let kicks = match current.as_mut() {
+            Option::Some(def) => &mut def.kicks,
+            Option::None => &mut set_kicks,
+        };
+        if key == "kicks" {
+            kicks.default = parse_offsets(value, line_number)?;
+        } else if let Option::Some(turn) = key.strip_prefix("kicks ") {
+            let mut turn = turn.split('>').map(parse_rotation);
+            let (from, to) = match (turn.next(), turn.next(), turn.next()) {
+                (Option::Some(Option::Some(from)), Option::Some(Option::Some(to)), Option::None) => {
+                    (from, to)
+                },
+                _ => return Result::Err(PieceSetError::InvalidLine(line_number))
+            };
+            kicks.transitions.push(((from, to), parse_offsets(value, line_number)?));
+        } else if key == "name" && current.is_none() {
+            name = value.to_string();
+        } else {
+            let def = current.as_mut().ok_or(PieceSetError::NoPiece(line_number))?;
+            match key {
+                "color" => {
+                    def.color = Color::from_hex(value)
+                        .ok_or(PieceSetError::InvalidLine(line_number))?
+                },
+                "spawn" => {
+                    def.spawn = match parse_offsets(value, line_number)?.as_slice() {
+                        [offset] => *offset,
+                        _ => return Result::Err(PieceSetError::InvalidLine(line_number))
+                    }
+                },
+                _ => return Result::Err(PieceSetError::InvalidLine(line_number))
+            }
+        }
+    }
+    if let Option::Some(done) = current.take() {
+        defs.push(done.build()?);
+    }
+    if defs.is_empty() {
+        return Result::Err(PieceSetError::EmptySet)
+    }
+    Result::Ok((name, defs))
+}
+
+fn intern(def: PieceDef) -> &'static PieceDef {
+    let mut defs = CUSTOM_DEFS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Option::Some(known) = defs.iter().find(|known| ***known == def) {
+        return known
+    }
+    let def: &'static PieceDef = Box::leak(Box::new(def));
+    defs.push(def);
+    def
+}
+
+impl PieceSet {
+    /// The seven standard tetrominoes.
+    pub fn tetrominoes() -> PieceSet {
+        PieceSet {
+            name: String::from("tetrominoes"),
+            pieces: PIECE_TYPES.to_vec(),
+        }
+    }
+
+    /// The twelve pentominoes.
+    pub fn pentominoes() -> PieceSet {
+        PENTOMINOES.get_or_init(|| {
+            PieceSet::parse(include_str!("../../pieces/pentominoes.txt"))
+                .expect("built in pentomino set is valid")
+        }).clone()
+    }
+
+    /// A tiny set with the two trominoes.
+    pub fn trominoes() -> PieceSet {
+        TROMINOES.get_or_init(|| {
+            PieceSet::parse(include_str!("../../pieces/trominoes.txt"))
+                .expect("built in tromino set is valid")
+        }).clone()
+    }
+
+    /// A built in set by name.
+    pub fn builtin(name: &str) -> Option<PieceSet> {
+        match name {
+            "tetrominoes" => Option::Some(PieceSet::tetrominoes()),
+            "pentominoes" => Option::Some(PieceSet::pentominoes()),
+            "trominoes" => Option::Some(PieceSet::trominoes()),
+            _ => Option::None
+        }
+    }
+
+    /// Reads a set. Its pieces are `PieceType::Custom` even if they share a
+    /// letter with a standard piece, so rules tied to the standard pieces
+    /// like T-spins don't apply to them. Parsed pieces are kept for as long
+    /// as the program runs, each distinct piece only once.
+    pub fn parse(text: &str) -> Result<PieceSet, PieceSetError> {
+        let (name, defs) = parse_defs(text)?;
+        Result::Ok(PieceSet {
+            name,
+            pieces: defs.into_iter()
+                .map(|def| PieceType::Custom(intern(def)))
+                .collect(),
+        })
+    }
+
+    /// Reads the set file at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<PieceSet, PieceSetError> {
+        let text = fs::read_to_string(path).map_err(PieceSetError::IOError)?;
+        PieceSet::parse(&text)
+    }
+
+    /// The pieces in the order they were written, never empty.
+    pub fn pieces(&self) -> &[PieceType] {
+        &self.pieces
+    }
+
+    /// The piece of the set called `letter`.
+    pub fn by_letter(&self, letter: char) -> Option<PieceType> {
+        self.pieces.iter().cloned().find(|p| p.letter() == letter)
+    }
+}
+
+impl Default for PieceSet {
+    fn default() -> PieceSet {
+        PieceSet::tetrominoes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SET: &str = "name: pair\npiece: D\ncolor: #ff0000\n@#\n";
+
+    #[test]
+    fn parsing_a_set_again_reuses_its_pieces() {
+        let first = PieceSet::parse(SET).unwrap();
+        let second = PieceSet::parse(SET).unwrap();
+        assert_eq!(first, second);
+        assert!(std::ptr::eq(first.pieces[0].def(), second.pieces[0].def()));
+
+        let other = PieceSet::parse(&SET.replace("ff0000", "00ff00")).unwrap();
+        assert!(!std::ptr::eq(first.pieces[0].def(), other.pieces[0].def()));
+    }
+
+    #[test]
+    fn single_states_are_turned_into_the_others() {
+        let set = PieceSet::parse(SET).unwrap();
+        let def = set.pieces[0].def();
+        assert_eq!(set.name, "pair");
+        assert_eq!(def.states, vec![vec![(0,0), (1,0)], vec![(0,0), (0,1)]]);
+        assert_eq!(def.size(), 2);
+        assert_eq!(def.kicks.kicks(Rotation::Up, Rotation::Right), &[(0,0)]);
+    }
+
+    #[test]
+    fn bad_sets_are_rejected() {
+        assert!(matches!(PieceSet::parse(""), Result::Err(PieceSetError::EmptySet)));
+        assert!(matches!(PieceSet::parse("@#\n"), Result::Err(PieceSetError::NoPiece(1))));
+        assert!(matches!(
+            PieceSet::parse("piece: D\n##\n"),
+            Result::Err(PieceSetError::MissingOrigin(2))
+        ));
+        assert!(matches!(
+            PieceSet::parse("piece: X\n+\n"),
+            Result::Err(PieceSetError::EmptyState(2))
+        ));
+        assert!(matches!(
+            PieceSet::parse("name: empty\nkicks: 0,0\n"),
+            Result::Err(PieceSetError::EmptySet)
+        ));
+    }
+}
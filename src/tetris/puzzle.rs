@@ -0,0 +1,404 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use crate::tetris::config::GameConfig;
+use crate::tetris::event::{Event, TSpin};
+use crate::tetris::manager::TetrisManager;
+use crate::tetris::notation::{NotationError, Setup};
+use crate::tetris::piece::PieceType;
+
+/// What a puzzle asks for.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Goal {
+    /// Clear this many rows in total.
+    ClearLines(u32),
+    /// Leave the field empty after a clear.
+    PerfectClear,
+    /// A full T-spin clearing exactly this many rows.
+    TSpin(u32),
+    /// Place every piece without topping out.
+    Survive,
+}
+
+/// How a puzzle attempt is going.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PuzzleState {
+    /// The goal isn't reached yet and still can be.
+    Playing,
+    /// The goal was reached.
+    Solved,
+    /// The goal can't be reached anymore.
+    Failed,
+}
+
+/// Why a puzzle couldn't be loaded.
+#[derive(Debug)]
+pub enum PuzzleError {
+    /// The file couldn't be read.
+    IOError(std::io::Error),
+    /// The board or queue is malformed.
+    NotationError(NotationError),
+    /// There is no `goal:` header.
+    MissingGoal,
+    /// The `goal:` header names no known goal.
+    UnknownGoal(String),
+}
+
+impl std::error::Error for PuzzleError {}
+
+impl std::fmt::Display for PuzzleError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            PuzzleError::IOError(e) => write!(fmt, "{}", e),
+            PuzzleError::NotationError(e) => write!(fmt, "{}", e),
+            PuzzleError::MissingGoal => write!(fmt, "puzzle has no goal"),
+            PuzzleError::UnknownGoal(g) => write!(fmt, "unknown goal '{}'", g),
+        }
+    }
+}
+
+impl Goal {
+    fn parse(text: &str) -> Result<Goal, PuzzleError> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let goal = match words.as_slice() {
+            ["lines", n] => n.parse().ok().map(Goal::ClearLines),
+            ["perfect", "clear"] => Option::Some(Goal::PerfectClear),
+            ["tspin", "single"] => Option::Some(Goal::TSpin(1)),
+            ["tspin", "double"] => Option::Some(Goal::TSpin(2)),
+            ["tspin", "triple"] => Option::Some(Goal::TSpin(3)),
+            ["survive"] => Option::Some(Goal::Survive),
+            _ => Option::None
+        };
+        goal.ok_or_else(|| PuzzleError::UnknownGoal(text.to_string()))
+    }
+
+    /// The goal as shown to the player.
+    pub fn describe(&self) -> String {
+        match self {
+            Goal::ClearLines(n) => format!("Clear {} lines", n),
+            Goal::PerfectClear => String::from("Perfect clear"),
+            Goal::TSpin(1) => String::from("T-spin single"),
+            Goal::TSpin(2) => String::from("T-spin double"),
+            Goal::TSpin(_) => String::from("T-spin triple"),
+            Goal::Survive => String::from("Place every piece"),
+        }
+    }
+}
+
+/// A fixed board and piece sequence with a goal.
+///
+/// Puzzle files use the board notation from `Setup` with two extra headers,
+/// `name:` and `goal:`. Goals are `lines N`, `perfect clear`,
+/// `tspin single|double|triple` and `survive`.
+#[derive(Clone, Debug)]
+pub struct Puzzle {
+    /// The file name without extension, progress is kept by it.
+    pub id: String,
+    /// The `name:` header, the id if there is none.
+    pub name: String,
+    /// What has to be done.
+    pub goal: Goal,
+    /// The board, hold and queue to start from.
+    pub setup: Setup,
+}
+
+impl Puzzle {
+    /// Reads a puzzle from `text`, identified by `id`.
+    pub fn parse(id: &str, text: &str) -> Result<Puzzle, PuzzleError> {
+        let mut name = String::from(id);
+        let mut goal = Option::None;
+        let mut board = String::new();
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if let Option::Some(rest) = trimmed.strip_prefix("name:") {
+                name = rest.trim().to_string();
+            } else if let Option::Some(rest) = trimmed.strip_prefix("goal:") {
+                goal = Option::Some(Goal::parse(rest)?);
+            } else {
+                board.push_str(line);
+                board.push('\n');
+            }
+        }
+        Result::Ok(Puzzle {
+            id: id.to_string(),
+            name,
+            goal: goal.ok_or(PuzzleError::MissingGoal)?,
+            setup: board.parse().map_err(PuzzleError::NotationError)?,
+        })
+    }
+
+    /// Reads the puzzle file at `path`, identified by its file name.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Puzzle, PuzzleError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(PuzzleError::IOError)?;
+        let id = path.file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Puzzle::parse(&id, &text)
+    }
+
+    /// Loads every `.txt` file in `dir`, ordered by file name.
+    pub fn load_dir<P: AsRef<Path>>(dir: P) -> Result<Vec<Puzzle>, PuzzleError> {
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir).map_err(PuzzleError::IOError)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+            .collect();
+        paths.sort();
+        paths.iter().map(Puzzle::load).collect()
+    }
+
+    /// A game from the puzzle's setup played with the rules of `config`,
+    /// see `TetrisManager::from_setup`.
+    pub fn start(&self, config: &GameConfig) -> (TetrisManager, PuzzleRun) {
+        (TetrisManager::from_setup(&self.setup, config), PuzzleRun::new(self.goal))
+    }
+}
+
+/// Follows a game started from a puzzle and decides when it is solved or
+/// failed. Feed it every batch of events drained from the manager.
+pub struct PuzzleRun {
+    goal: Goal,
+    lines: u32,
+    state: PuzzleState,
+}
+
+impl PuzzleRun {
+    /// An attempt at `goal` that just started.
+    pub fn new(goal: Goal) -> PuzzleRun {
+        PuzzleRun {
+            goal,
+            lines: 0,
+            state: PuzzleState::Playing,
+        }
+    }
+
+    /// How the attempt is going.
+    pub fn state(&self) -> PuzzleState {
+        self.state
+    }
+
+    /// The goal of the puzzle.
+    pub fn goal(&self) -> Goal {
+        self.goal
+    }
+
+    /// Updates the state from a batch of events drained from `manager`, and
+    /// returns it.
+    pub fn observe(
+        &mut self,
+        manager: &TetrisManager,
+        events: &[Event]
+    ) -> PuzzleState {
+        if self.state != PuzzleState::Playing {
+            return self.state
+        }
+        for event in events {
+            match event {
+                Event::LinesCleared(n) => {
+                    self.lines += n;
+                    match self.goal {
+                        Goal::ClearLines(goal) if self.lines >= goal => {
+                            self.state = PuzzleState::Solved
+                        },
+                        _ => ()
+                    }
+                },
+                Event::PerfectClear
+                    if self.goal == Goal::PerfectClear => {
+                        self.state = PuzzleState::Solved
+                    },
+                Event::TSpin(TSpin::Full, n)
+                    if self.goal == Goal::TSpin(*n) => {
+                        self.state = PuzzleState::Solved
+                    },
+                Event::OutOfPieces => {
+                    self.state = match self.goal {
+                        Goal::Survive => PuzzleState::Solved,
+                        _ => PuzzleState::Failed,
+                    }
+                },
+                Event::GameOver => self.state = PuzzleState::Failed,
+                _ => ()
+            }
+            if self.state != PuzzleState::Playing {
+                return self.state
+            }
+        }
+        if !self.possible(manager) {
+            self.state = PuzzleState::Failed;
+        }
+        self.state
+    }
+
+    // Rough upper bound on whether the goal can still be met with the pieces
+    // left. It never fails a puzzle that can be solved.
+    fn possible(&self, manager: &TetrisManager) -> bool {
+        if manager.queue().is_endless() {
+            return true
+        }
+        let mut pieces: Vec<PieceType> = manager.queue().upcoming().iter()
+            .map(|p| p.piece_type())
+            .collect();
+        pieces.push(manager.current().piece_type());
+        pieces.extend(manager.held().map(|p| p.piece_type()));
+
+        let field = manager.field();
+        let filled = field.get_blocks().len() as u32;
+        let cells = filled + pieces.iter().map(|p| p.size() as u32).sum::<u32>();
+        let width = field.width() as u32;
+        match self.goal {
+            Goal::ClearLines(goal) => self.lines + cells / width >= goal,
+            Goal::PerfectClear => {
+                let rows = (0 .. field.height())
+                    .filter(|y| (0 .. field.width()).any(|x| field.contains_node(x, *y)))
+                    .count() as u32;
+                cells >= rows * width
+            },
+            Goal::TSpin(_) => pieces.contains(&PieceType::T),
+            Goal::Survive => true,
+        }
+    }
+}
+
+/// Ids of solved puzzles, kept in a plain text file with one id per line.
+pub struct PuzzleProgress {
+    path: PathBuf,
+    solved: BTreeSet<String>,
+}
+
+impl PuzzleProgress {
+    /// Reads progress from `path`, a missing file means nothing is solved.
+    pub fn load<P: AsRef<Path>>(path: P) -> PuzzleProgress {
+        let solved = fs::read_to_string(path.as_ref())
+            .map(|text| text.lines().map(|l| l.trim().to_string())
+                 .filter(|l| !l.is_empty())
+                 .collect())
+            .unwrap_or_default();
+        PuzzleProgress {
+            path: path.as_ref().to_path_buf(),
+            solved,
+        }
+    }
+
+    /// Whether the puzzle with `id` was solved before.
+    pub fn is_solved(&self, id: &str) -> bool {
+        self.solved.contains(id)
+    }
+
+    /// Marks the puzzle with `id` as solved and saves the progress.
+    pub fn mark_solved(&mut self, id: &str) -> std::io::Result<()> {
+        if self.solved.insert(id.to_string()) {
+            self.save()
+        } else {
+            Result::Ok(())
+        }
+    }
+
+    /// Writes to a temporary file next to the progress file and renames it
+    /// over, so a crash halfway leaves the old progress intact.
+    pub fn save(&self) -> std::io::Result<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        {
+            let mut file = fs::File::create(&tmp)?;
+            for id in self.solved.iter() {
+                writeln!(file, "{}", id)?;
+            }
+            file.sync_all()?;
+        }
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::input::Action;
+
+    // Four rows with a gap for a flat I in the bottom right
+    const BOARD: &str = "..........\n..........\n..........\n######....";
+
+    fn start(goal: &str, queue: &str) -> (TetrisManager, PuzzleRun) {
+        let text = format!("goal: {}\nqueue: {}\n{}", goal, queue, BOARD);
+        Puzzle::parse("test", &text).unwrap().start(&GameConfig::default())
+    }
+
+    fn play(manager: &mut TetrisManager, run: &mut PuzzleRun, actions: &[Action]) -> PuzzleState {
+        for action in actions {
+            manager.apply(*action);
+        }
+        let events = manager.drain_events();
+        run.observe(manager, &events)
+    }
+
+    #[test]
+    fn goals_and_names_are_read_from_the_headers() {
+        let goal = |text: &str| {
+            Puzzle::parse("p", &format!("goal: {}\nqueue: T\n....", text)).map(|p| p.goal)
+        };
+        assert_eq!(goal("lines 2").unwrap(), Goal::ClearLines(2));
+        assert_eq!(goal("perfect clear").unwrap(), Goal::PerfectClear);
+        assert_eq!(goal("tspin double").unwrap(), Goal::TSpin(2));
+        assert_eq!(goal("  survive ").unwrap(), Goal::Survive);
+        assert!(matches!(goal("tspin quad"), Result::Err(PuzzleError::UnknownGoal(_))));
+        assert!(matches!(goal("lines many"), Result::Err(PuzzleError::UnknownGoal(_))));
+
+        let missing = Puzzle::parse("p", "queue: T\n....");
+        assert!(matches!(missing, Result::Err(PuzzleError::MissingGoal)));
+        let unnamed = Puzzle::parse("tsd-1", "goal: survive\n....").unwrap();
+        assert_eq!(unnamed.name, "tsd-1");
+        let named = Puzzle::parse("tsd-1", "name: First TSD\ngoal: survive\n....").unwrap();
+        assert_eq!(named.name, "First TSD");
+    }
+
+    #[test]
+    fn clearing_enough_lines_solves_the_puzzle() {
+        let (mut manager, mut run) = start("lines 1", "I");
+        assert_eq!(play(&mut manager, &mut run, &[]), PuzzleState::Playing);
+        let state = play(&mut manager, &mut run, &[Action::DasRight, Action::HardDrop]);
+        assert_eq!(state, PuzzleState::Solved);
+        // Later events don't change the outcome
+        assert_eq!(run.observe(&manager, &[Event::GameOver]), PuzzleState::Solved);
+    }
+
+    #[test]
+    fn running_out_of_pieces_fails_unless_surviving_is_the_goal() {
+        let (mut manager, mut run) = start("lines 1", "I");
+        assert_eq!(play(&mut manager, &mut run, &[Action::HardDrop]), PuzzleState::Failed);
+
+        let (mut manager, mut run) = start("survive", "I");
+        assert_eq!(play(&mut manager, &mut run, &[Action::HardDrop]), PuzzleState::Solved);
+    }
+
+    #[test]
+    fn impossible_goals_fail_right_away() {
+        let (mut manager, mut run) = start("tspin double", "IO");
+        assert_eq!(play(&mut manager, &mut run, &[]), PuzzleState::Failed);
+
+        // The six blocks and two pieces make fourteen cells, one row at most
+        let (mut manager, mut run) = start("lines 3", "IO");
+        assert_eq!(play(&mut manager, &mut run, &[]), PuzzleState::Failed);
+        let (mut manager, mut run) = start("lines 1", "IO");
+        assert_eq!(play(&mut manager, &mut run, &[]), PuzzleState::Playing);
+    }
+
+    #[test]
+    fn progress_survives_a_save_and_load() {
+        let dir = std::env::temp_dir().join(format!("tetris-progress-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("progress.txt");
+
+        let mut progress = PuzzleProgress::load(&path);
+        assert!(!progress.is_solved("tsd-1"));
+        progress.mark_solved("tsd-1").unwrap();
+        progress.mark_solved("pc-2").unwrap();
+
+        let loaded = PuzzleProgress::load(&path);
+        assert!(loaded.is_solved("tsd-1"));
+        assert!(loaded.is_solved("pc-2"));
+        assert!(!dir.join("progress.txt.tmp").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::VecDeque;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use crate::tetris::piece::Piece;
+use crate::tetris::pieceset::PieceSet;
+use crate::tetris::skin::Skin;
+
+/// Bag randomizer, every piece type of the set is dealt once per bag so with
+/// the seven tetrominoes droughts can't get longer than twelve pieces. A
+/// fixed queue deals a given sequence instead and then runs dry.
+///
+/// A clone keeps the randomizer where it was, so it deals the same pieces.
+#[derive(Clone)]
+pub struct PieceQueue {
+    upcoming: VecDeque<Piece>,
+    rng: StdRng,
+    set: PieceSet,
+    skin: Skin,
+    preview: usize,
+    endless: bool,
+}
+
+impl PieceQueue {
+    /// An endless queue dealing pieces from `set` colored by `skin`.
+    pub fn new(seed: u64, preview: usize, set: PieceSet, skin: Skin) -> PieceQueue {
+        let mut queue = PieceQueue {
+            upcoming: VecDeque::new(),
+            rng: StdRng::seed_from_u64(seed),
+            set,
+            skin,
+            preview,
+            endless: true,
+        };
+        queue.fill();
+        queue
+    }
+
+    /// A queue of exactly `pieces`, for setups and puzzles.
+    pub fn fixed(pieces: Vec<Piece>, preview: usize) -> PieceQueue {
+        PieceQueue {
+            upcoming: pieces.into_iter().collect(),
+            rng: StdRng::seed_from_u64(0),
+            set: PieceSet::default(),
+            skin: Skin::default(),
+            preview,
+            endless: false,
+        }
+    }
+
+    fn fill(&mut self) {
+        while self.endless && self.upcoming.len() <= self.preview {
+            let mut bag = self.set.pieces().to_vec();
+            bag.shuffle(&mut self.rng);
+            for piece_type in bag.iter() {
+                let color = self.skin.color_of(*piece_type);
+                self.upcoming.push_back(Piece::new(*piece_type, color, (0,0)));
+            }
+        }
+    }
+
+    /// Colors every queued piece and the ones dealt later with `skin`.
+    pub fn recolor(&mut self, skin: &Skin) {
+        for piece in self.upcoming.iter_mut() {
+            *piece = piece.with_color(skin.color_of(piece.piece_type()));
+        }
+        self.skin = skin.clone();
+    }
+
+    /// Whether new bags are dealt once the queue runs low.
+    pub fn is_endless(&self) -> bool {
+        self.endless
+    }
+
+    /// Whether every piece was taken, only a fixed queue runs out.
+    pub fn is_empty(&self) -> bool {
+        self.upcoming.is_empty()
+    }
+
+    /// Every piece dealt so far but not yet taken, for a fixed queue this is
+    /// the rest of the sequence.
+    pub fn upcoming(&self) -> Vec<Piece> {
+        self.upcoming.iter().cloned().collect()
+    }
+
+    /// The upcoming pieces, next one first.
+    pub fn preview(&self) -> Vec<Piece> {
+        self.upcoming.iter().take(self.preview).cloned().collect()
+    }
+}
+
+impl Iterator for PieceQueue {
+    type Item = Piece;
+
+    /// Takes the next piece off the queue, `None` once a fixed queue is empty.
+    fn next(&mut self) -> Option<Piece> {
+        let piece = self.upcoming.pop_front();
+        self.fill();
+        piece
+    }
+}
@@ -0,0 +1,268 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use crate::tetris::config::{GameConfig, Mode};
+use crate::tetris::manager::TetrisManager;
+use crate::tetris::modifier::Modifier;
+use crate::tetris::replay::Replay;
+
+/// One finished game.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Record {
+    /// The mode the game was played in.
+    pub mode: Mode,
+    /// Milliseconds for sprint, the level for master and the score for the
+    /// other modes.
+    pub value: u64,
+    /// Seconds since the Unix epoch when the game ended.
+    pub date: u64,
+    /// Seed of the piece queue.
+    pub seed: u64,
+    /// `GameConfig::config_hash` of the settings the game was played with.
+    pub config_hash: u64,
+    /// Path of the replay file, if one was saved.
+    pub replay: Option<PathBuf>,
+    /// Modifiers the game was played with, missing in older databases.
+    #[serde(default)]
+    pub modifiers: Vec<Modifier>,
+}
+
+impl Record {
+    /// A record dated now, without a replay.
+    pub fn new(mode: Mode, value: u64, seed: u64, config_hash: u64) -> Record {
+        let date = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Record {
+            mode,
+            value,
+            date,
+            seed,
+            config_hash,
+            replay: Option::None,
+            modifiers: Vec::new(),
+        }
+    }
+
+    /// The result of `manager`'s game, which was started with `config` and
+    /// took `elapsed`. `None` for practice, and for sprints that ended
+    /// before all their lines were cleared.
+    pub fn of_game(
+        config: &GameConfig,
+        manager: &TetrisManager,
+        elapsed: Duration
+    ) -> Option<Record> {
+        let mode = config.mode;
+        if mode.line_goal().is_some_and(|goal| manager.lines() < goal) {
+            return Option::None
+        }
+        let value = match mode {
+            Mode::Sprint => elapsed.as_millis() as u64,
+            Mode::Marathon | Mode::Ultra => manager.score() as u64,
+            Mode::Master => manager.level() as u64,
+            Mode::Practice => return Option::None,
+        };
+        let mut record = Record::new(mode, value, manager.seed(), config.config_hash());
+        record.modifiers = config.modifiers.clone();
+        Option::Some(record)
+    }
+
+    /// Whether this result beats `other`, ties go to the older record.
+    pub fn beats(&self, other: &Record) -> bool {
+        if self.mode.lower_is_better() {
+            self.value < other.value
+        } else {
+            self.value > other.value
+        }
+    }
+}
+
+// The order modifiers were picked in doesn't matter
+fn same_modifiers(a: &[Modifier], b: &[Modifier]) -> bool {
+    a.len() == b.len() && a.iter().all(|m| b.contains(m))
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct RecordFile {
+    records: Vec<Record>,
+}
+
+/// Local results for every mode, stored as JSON.
+///
+/// Every change is written to a temporary file next to the database and
+/// renamed over it, so a crash leaves either the old or the new file.
+pub struct Records {
+    path: PathBuf,
+    records: Vec<Record>,
+}
+
+impl Records {
+    /// Reads the database at `path`, a missing file is an empty database.
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Records> {
+        let path = path.as_ref();
+        let file: RecordFile = match fs::read_to_string(path) {
+            Result::Ok(text) => serde_json::from_str(&text)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+            Result::Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => {
+                RecordFile::default()
+            },
+            Result::Err(e) => return Result::Err(e)
+        };
+        Result::Ok(Records {
+            path: path.to_path_buf(),
+            records: file.records,
+        })
+    }
+
+    /// The best `n` results in `mode` played with the same settings and
+    /// modifiers, best first.
+    pub fn top(
+        &self,
+        mode: Mode,
+        config_hash: u64,
+        modifiers: &[Modifier],
+        n: usize
+    ) -> Vec<&Record> {
+        let mut res: Vec<&Record> = self.records.iter()
+            .filter(|r| r.mode == mode && r.config_hash == config_hash)
+            .filter(|r| same_modifiers(&r.modifiers, modifiers))
+            .collect();
+        // Stable, so equal results stay in the order they were set
+        res.sort_by(|a, b| {
+            if a.beats(b) {
+                std::cmp::Ordering::Less
+            } else if b.beats(a) {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        });
+        res.truncate(n);
+        res
+    }
+
+    /// The best result in `mode` with the same settings and modifiers.
+    pub fn personal_best(
+        &self,
+        mode: Mode,
+        config_hash: u64,
+        modifiers: &[Modifier]
+    ) -> Option<&Record> {
+        self.top(mode, config_hash, modifiers, 1).first().cloned()
+    }
+
+    /// Stores a result and saves the database. Returns whether it's a new
+    /// personal best for its mode, settings and modifiers.
+    pub fn add(&mut self, record: Record) -> std::io::Result<bool> {
+        let best = self.personal_best(record.mode, record.config_hash, &record.modifiers)
+            .is_none_or(|best| record.beats(best));
+        self.records.push(record);
+        self.save()?;
+        Result::Ok(best)
+    }
+
+    /// Stores a result like `add`, with its replay saved in a `replays`
+    /// directory next to the database.
+    pub fn add_with_replay(
+        &mut self,
+        mut record: Record,
+        replay: &Replay
+    ) -> std::io::Result<bool> {
+        let name = format!("{}-{}-{}.json", record.mode.name(), record.date, record.seed);
+        let path = self.path.with_file_name("replays").join(name);
+        replay.save(&path)?;
+        record.replay = Option::Some(path);
+        self.add(record)
+    }
+
+    /// Writes the database, see `Records`.
+    pub fn save(&self) -> std::io::Result<()> {
+        let file = RecordFile { records: self.records.clone() };
+        let json = serde_json::to_string_pretty(&file)
+            .map_err(std::io::Error::other)?;
+        if let Option::Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        {
+            let mut out = fs::File::create(&tmp)?;
+            out.write_all(json.as_bytes())?;
+            out.sync_all()?;
+        }
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::input::Action;
+
+    fn record(value: u64, config_hash: u64, modifiers: &[Modifier]) -> Record {
+        Record {
+            modifiers: modifiers.to_vec(),
+            .. Record::new(Mode::Marathon, value, 0, config_hash)
+        }
+    }
+
+    #[test]
+    fn personal_bests_are_kept_per_settings_and_modifiers() {
+        let dir = std::env::temp_dir().join(format!("tetris-records-{}", std::process::id()));
+        let mut records = Records::load(dir.join("records.json")).unwrap();
+        let both = [Modifier::Big, Modifier::NoHold];
+
+        assert!(records.add(record(100, 1, &[])).unwrap());
+        assert!(records.add(record(50, 2, &[])).unwrap());
+        assert!(records.add(record(10, 1, &both)).unwrap());
+        assert!(!records.add(record(5, 1, &[Modifier::NoHold, Modifier::Big])).unwrap());
+        assert!(!records.add(record(80, 1, &[])).unwrap());
+
+        let best = |hash, modifiers| {
+            records.personal_best(Mode::Marathon, hash, modifiers).map(|r| r.value)
+        };
+        assert_eq!(best(1, &[]), Option::Some(100));
+        assert_eq!(best(2, &[]), Option::Some(50));
+        assert_eq!(best(1, &both), Option::Some(10));
+        assert_eq!(best(1, &[Modifier::Big]), Option::None);
+        assert_eq!(best(3, &[]), Option::None);
+
+        let top: Vec<u64> = records.top(Mode::Marathon, 1, &[], 5).iter()
+            .map(|r| r.value)
+            .collect();
+        assert_eq!(top, vec![100, 80]);
+        assert_eq!(records.top(Mode::Sprint, 1, &[], 5), Vec::<&Record>::new());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn results_are_saved_with_their_replays() {
+        let dir = std::env::temp_dir().join(format!("tetris-replays-{}", std::process::id()));
+        let mut records = Records::load(dir.join("records.json")).unwrap();
+        let config = GameConfig::default().seed(4).modifier(Modifier::NoHold);
+        let mut manager = TetrisManager::with_config(&config);
+        let mut replay = Replay::new(&config, &manager);
+        replay.apply(&mut manager, Action::HardDrop);
+
+        let record = Record::of_game(&config, &manager, Duration::from_secs(1)).unwrap();
+        assert_eq!(record.modifiers, vec![Modifier::NoHold]);
+        assert!(records.add_with_replay(record, &replay).unwrap());
+
+        let best = records.personal_best(Mode::Marathon, config.config_hash(), &[Modifier::NoHold])
+            .unwrap();
+        let path = best.replay.as_ref().unwrap();
+        assert!(path.starts_with(dir.join("replays")));
+        let saved = Replay::load(path).unwrap();
+        assert_eq!(saved.modifiers, vec![Modifier::NoHold]);
+        assert_eq!(saved.play(&config).unwrap().field(), manager.field());
+
+        let sprint = GameConfig::default().mode(Mode::Sprint);
+        let unfinished = TetrisManager::with_config(&sprint);
+        assert_eq!(Record::of_game(&sprint, &unfinished, Duration::from_secs(1)), Option::None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,172 @@
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::tetris::config::{GameConfig, Mode};
+use crate::tetris::input::Action;
+use crate::tetris::manager::TetrisManager;
+use crate::tetris::modifier::Modifier;
+
+/// The inputs of a game and the frames they came on, enough to play it again
+/// from its seed. Frontends pass their inputs and frames through the replay
+/// so it sees everything the manager does.
+///
+/// Undo and redo aren't recorded, practice games have no replays.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Replay {
+    /// The mode the game was played in.
+    pub mode: Mode,
+    /// Seed of the piece queue.
+    pub seed: u64,
+    /// `GameConfig::config_hash` of the settings the game was played with,
+    /// it can only be played again with the same ones.
+    pub config_hash: u64,
+    /// Modifiers the game was played with.
+    pub modifiers: Vec<Modifier>,
+    /// Inputs with the number of frames played before them, in order.
+    pub inputs: Vec<(u64, Action)>,
+    /// Frames played until the game ended.
+    pub frames: u64,
+    /// Whether the game was ended by `TetrisManager::finish`, like at the
+    /// time limit of ultra.
+    pub finished: bool,
+}
+
+impl Replay {
+    /// An empty replay of `manager`, which was just started with `config`.
+    pub fn new(config: &GameConfig, manager: &TetrisManager) -> Replay {
+        Replay {
+            mode: config.mode,
+            seed: manager.seed(),
+            config_hash: config.config_hash(),
+            modifiers: config.modifiers.clone(),
+            inputs: Vec::new(),
+            frames: 0,
+            finished: false,
+        }
+    }
+
+    /// Applies `action` to `manager` and records it.
+    pub fn apply(&mut self, manager: &mut TetrisManager, action: Action) {
+        if !manager.is_game_over() {
+            self.inputs.push((self.frames, action));
+        }
+        manager.apply(action);
+    }
+
+    /// Advances `manager` by a frame and counts it.
+    pub fn frame(&mut self, manager: &mut TetrisManager) {
+        if !manager.is_game_over() {
+            self.frames += 1;
+        }
+        manager.frame();
+    }
+
+    /// Ends the game with `TetrisManager::finish` and records that.
+    pub fn finish(&mut self, manager: &mut TetrisManager) {
+        if !manager.is_game_over() {
+            self.finished = true;
+        }
+        manager.finish();
+    }
+
+    /// Plays the game again with `config`, returning it as it ended. `None`
+    /// if `config` isn't what the game was played with.
+    pub fn play(&self, config: &GameConfig) -> Option<TetrisManager> {
+        if config.config_hash() != self.config_hash {
+            return Option::None
+        }
+        let mut manager = TetrisManager::with_config(&config.clone().seed(self.seed));
+        let mut inputs = self.inputs.iter().peekable();
+        for frame in 0..=self.frames {
+            while let Option::Some((_, action)) = inputs.next_if(|(at, _)| *at == frame) {
+                manager.apply(*action);
+            }
+            if frame < self.frames {
+                manager.frame();
+            }
+        }
+        if self.finished {
+            manager.finish();
+        }
+        Option::Some(manager)
+    }
+
+    /// Reads a replay saved by `save`.
+    pub fn load<P: AsRef<Path>>(path: P) -> std::io::Result<Replay> {
+        let text = fs::read_to_string(path)?;
+        serde_json::from_str(&text)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    /// Writes the replay as JSON, creating missing parent directories.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        if let Option::Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string(self)
+            .map_err(std::io::Error::other)?;
+        fs::write(path, json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(config: &GameConfig) -> (TetrisManager, Replay) {
+        let mut manager = TetrisManager::with_config(config);
+        let mut replay = Replay::new(config, &manager);
+        let moves = [Action::MoveLeft, Action::RotateRight, Action::MoveRight, Action::Hold];
+        for (i, action) in moves.iter().cycle().take(40).enumerate() {
+            replay.apply(&mut manager, *action);
+            if i % 3 == 0 {
+                replay.apply(&mut manager, Action::HardDrop);
+            }
+            for _ in 0..i % 5 {
+                replay.frame(&mut manager);
+            }
+        }
+        (manager, replay)
+    }
+
+    #[test]
+    fn replays_play_the_game_again() {
+        let config = GameConfig::default().seed(7).modifier(Modifier::Big);
+        let (manager, replay) = record(&config);
+        assert_eq!(replay.modifiers, vec![Modifier::Big]);
+        assert_eq!(replay.seed, 7);
+
+        let replayed = replay.play(&GameConfig::default().modifier(Modifier::Big)).unwrap();
+        assert_eq!(replayed.field(), manager.field());
+        assert_eq!(replayed.score(), manager.score());
+        assert_eq!(replayed.is_game_over(), manager.is_game_over());
+        assert!(replay.play(&GameConfig::default()).is_none());
+    }
+
+    #[test]
+    fn finished_games_stay_finished() {
+        let config = GameConfig::default().seed(2).mode(Mode::Ultra);
+        let (mut manager, mut replay) = record(&config);
+        let (frames, inputs) = (replay.frames, replay.inputs.len());
+        replay.finish(&mut manager);
+        replay.apply(&mut manager, Action::HardDrop);
+        replay.frame(&mut manager);
+
+        let replayed = replay.play(&config).unwrap();
+        assert!(replayed.is_game_over());
+        assert_eq!(replayed.field(), manager.field());
+        assert_eq!((replay.frames, replay.inputs.len()), (frames, inputs));
+    }
+
+    #[test]
+    fn saved_replays_load_the_same() {
+        let (_, replay) = record(&GameConfig::default().seed(5));
+        let path = std::env::temp_dir()
+            .join(format!("tetris-replay-{}", std::process::id()))
+            .join("replay.json");
+        replay.save(&path).unwrap();
+        assert_eq!(Replay::load(&path).unwrap(), replay);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
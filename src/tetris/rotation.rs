@@ -0,0 +1,346 @@
+//! Rotation systems and the kicks they try, found by name with `from_name`.
+
+use std::sync::OnceLock;
+use crate::tetris::gamefield::GameField;
+use crate::tetris::piece::{Piece, PieceType, Rotation};
+use crate::tetris::pieceset::{parse_defs, tetromino_defs, PieceDef};
+
+/// How the seven tetrominoes turn: their shape in every state and which
+/// offsets are tried when a turn is blocked. State 0 is the orientation
+/// pieces spawn in.
+///
+/// Pieces from other sets always turn the way their `PieceDef` says.
+pub trait RotationSystem {
+    /// The name `from_name` finds the system by.
+    fn name(&self) -> &'static str;
+
+    /// Definitions of the seven tetrominoes, found by letter.
+    fn defs(&self) -> &'static [PieceDef];
+
+    /// The definition of `piece_type`, custom pieces bring their own.
+    fn def(&self, piece_type: PieceType) -> &'static PieceDef {
+        match piece_type {
+            PieceType::Custom(def) => def,
+            standard => self.defs().iter()
+                .find(|def| def.letter == standard.letter())
+                .unwrap_or_else(|| standard.def())
+        }
+    }
+
+    /// Offsets tried in order when `piece` turns to `to` on `field`.
+    fn kicks(&self, _field: &GameField, piece: &Piece, to: Rotation) -> Vec<(i32,i32)> {
+        self.def(piece.piece_type()).kicks.kicks(piece.rotation(), to).to_vec()
+    }
+}
+
+impl PartialEq for dyn RotationSystem {
+    fn eq(&self, other: &dyn RotationSystem) -> bool {
+        self.name() == other.name()
+    }
+}
+
+impl Eq for dyn RotationSystem {}
+
+impl std::fmt::Debug for dyn RotationSystem {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(fmt, "{}", self.name())
+    }
+}
+
+/// Names of the built in systems.
+pub const SYSTEMS : [&str; 4] = ["srs", "ars", "nrs", "sega"];
+
+/// A built in system by its name in `SYSTEMS`.
+pub fn from_name(name: &str) -> Option<&'static dyn RotationSystem> {
+    match name {
+        "srs" => Option::Some(&Srs),
+        "ars" => Option::Some(&Ars),
+        "nrs" => Option::Some(&Nrs),
+        "sega" => Option::Some(&Sega),
+        _ => Option::None
+    }
+}
+
+/// Turns `piece` to `to`, trying the kicks of its rotation system in order.
+/// `None` if every offset is blocked.
+pub fn rotate(field: &GameField, piece: Piece, to: Rotation) -> Option<Piece> {
+    let turned = piece.with_rotation(to);
+    piece.system().kicks(field, &piece, to).iter()
+        .map(|(dx,dy)| turned.moved_by(*dx, *dy))
+        .find(|kicked| field.valid_piece(*kicked))
+}
+
+static ARS: OnceLock<Vec<PieceDef>> = OnceLock::new();
+static NRS: OnceLock<Vec<PieceDef>> = OnceLock::new();
+
+fn ars_defs() -> &'static [PieceDef] {
+    ARS.get_or_init(|| {
+        parse_defs(include_str!("../../rotations/ars.txt"))
+            .expect("built in ARS is valid").1
+    })
+}
+
+fn nrs_defs() -> &'static [PieceDef] {
+    NRS.get_or_init(|| {
+        parse_defs(include_str!("../../rotations/nrs.txt"))
+            .expect("built in NRS is valid").1
+    })
+}
+
+/// The Super Rotation System of guideline games, with five kicks per turn
+/// and separate ones for the I.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Srs;
+
+impl RotationSystem for Srs {
+    fn name(&self) -> &'static str {
+        "srs"
+    }
+
+    fn defs(&self) -> &'static [PieceDef] {
+        tetromino_defs()
+    }
+}
+
+/// The Arika Rotation System from TGM. Pieces spawn flat side up, sit at
+/// the bottom of their box and kick one column right, then left.
+///
+/// J, L and T don't kick when the first blocked cell of the turned piece,
+/// reading rows top to bottom, is in the center column, so they can't
+/// climb out of a well by turning.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Ars;
+
+impl RotationSystem for Ars {
+    fn name(&self) -> &'static str {
+        "ars"
+    }
+
+    fn defs(&self) -> &'static [PieceDef] {
+        ars_defs()
+    }
+
+    fn kicks(&self, field: &GameField, piece: &Piece, to: Rotation) -> Vec<(i32,i32)> {
+        let def = self.def(piece.piece_type());
+        let kicks = def.kicks.kicks(piece.rotation(), to).to_vec();
+        match piece.piece_type() {
+            PieceType::J | PieceType::L | PieceType::T => (),
+            _ => return kicks
+        }
+
+        let (x, y) = piece.position();
+        let mut cells = def.cells(to).to_vec();
+        cells.sort_unstable_by_key(|(cx, cy)| (*cy, *cx));
+        let first_blocked = cells.iter().find(|(cx, cy)| {
+            let cell = [(x + cx, y + cy)];
+            field.contains_any(&cell) || field.hits_wall(&cell) || field.hits_floor(&cell)
+        });
+        match first_blocked {
+            Option::Some((0, _)) => vec![(0,0)],
+            _ => kicks
+        }
+    }
+}
+
+/// The Nintendo Rotation System of the NES and Game Boy games. Turns never
+/// kick, and S, Z and I stand in the right half of their box.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Nrs;
+
+impl RotationSystem for Nrs {
+    fn name(&self) -> &'static str {
+        "nrs"
+    }
+
+    fn defs(&self) -> &'static [PieceDef] {
+        nrs_defs()
+    }
+}
+
+/// Sega's arcade Tetris, the shapes ARS was based on without any kicks.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Sega;
+
+impl RotationSystem for Sega {
+    fn name(&self) -> &'static str {
+        "sega"
+    }
+
+    fn defs(&self) -> &'static [PieceDef] {
+        ars_defs()
+    }
+
+    fn kicks(&self, _field: &GameField, piece: &Piece, to: Rotation) -> Vec<(i32,i32)> {
+        match piece.piece_type() {
+            PieceType::Custom(def) => def.kicks.kicks(piece.rotation(), to).to_vec(),
+            _ => vec![(0,0)]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::color::Color;
+    use crate::tetris::config::GameConfig;
+    use crate::tetris::gamefield::Block;
+    use crate::tetris::manager::TetrisManager;
+
+    // In the order of the expectations in the tables below
+    const SYSTEMS_UNDER_TEST: [&dyn RotationSystem; 4] = [&Srs, &Ars, &Nrs, &Sega];
+    const EMPTY: &str = "..........\n..........\n..........\n..........\n\
+                         ..........\n..........\n..........\n..........";
+
+    // A turn tried in every system: the piece starts in `from` at `at`, is
+    // pushed by `push` for as long as it fits, then turns to `to`. `kicked`
+    // is the offset the turn ended up at, `None` if it was blocked
+    struct Case {
+        name: &'static str,
+        piece_type: PieceType,
+        from: Rotation,
+        at: (i32,i32),
+        push: (i32,i32),
+        to: Rotation,
+        kicked: [Option<(i32,i32)>; 4],
+    }
+
+    const CASES: [Case; 6] = [
+        Case {
+            name: "free turn",
+            piece_type: PieceType::T,
+            from: Rotation::Up,
+            at: (4,3),
+            push: (0,0),
+            to: Rotation::Right,
+            kicked: [Option::Some((0,0)); 4],
+        },
+        Case {
+            name: "T against the right wall",
+            piece_type: PieceType::T,
+            from: Rotation::Left,
+            at: (4,3),
+            push: (1,0),
+            to: Rotation::Up,
+            kicked: [
+                Option::Some((-1,0)),
+                Option::Some((0,0)),
+                Option::Some((0,0)),
+                Option::Some((0,0)),
+            ],
+        },
+        Case {
+            name: "T against the left wall",
+            piece_type: PieceType::T,
+            from: Rotation::Left,
+            at: (4,3),
+            push: (-1,0),
+            to: Rotation::Up,
+            kicked: [
+                Option::Some((0,0)),
+                Option::Some((1,0)),
+                Option::None,
+                Option::None,
+            ],
+        },
+        Case {
+            name: "I against the left wall",
+            piece_type: PieceType::I,
+            from: Rotation::Right,
+            at: (4,3),
+            push: (-1,0),
+            to: Rotation::Up,
+            kicked: [
+                Option::Some((2,0)),
+                Option::None,
+                Option::None,
+                Option::None,
+            ],
+        },
+        Case {
+            name: "180 on the floor",
+            piece_type: PieceType::T,
+            from: Rotation::Up,
+            at: (4,3),
+            push: (0,1),
+            to: Rotation::Down,
+            kicked: [
+                Option::Some((0,-1)),
+                Option::Some((0,0)),
+                Option::Some((0,0)),
+                Option::Some((0,0)),
+            ],
+        },
+        Case {
+            name: "180 against the left wall",
+            piece_type: PieceType::T,
+            from: Rotation::Right,
+            at: (4,3),
+            push: (-1,0),
+            to: Rotation::Left,
+            kicked: [
+                Option::Some((1,0)),
+                Option::Some((0,0)),
+                Option::Some((0,0)),
+                Option::Some((0,0)),
+            ],
+        },
+    ];
+
+    fn try_case(case: &Case, system: &'static dyn RotationSystem) -> Option<(i32,i32)> {
+        let field: GameField = EMPTY.parse().unwrap();
+        let mut piece = Piece::new(case.piece_type, Color::GRAY, case.at)
+            .with_system(system)
+            .with_rotation(case.from);
+        let (dx, dy) = case.push;
+        while case.push != (0,0) && field.valid_piece(piece.moved_by(dx, dy)) {
+            piece = piece.moved_by(dx, dy);
+        }
+        let (x, y) = piece.position();
+        rotate(&field, piece, case.to).map(|turned| {
+            assert_eq!(turned.rotation(), case.to);
+            (turned.position().0 - x, turned.position().1 - y)
+        })
+    }
+
+    #[test]
+    fn every_system_turns_and_kicks_by_its_own_table() {
+        for case in CASES.iter() {
+            for (system, kicked) in SYSTEMS_UNDER_TEST.iter().zip(case.kicked.iter()) {
+                assert_eq!(try_case(case, *system), *kicked, "{} in {:?}", case.name, system);
+            }
+        }
+    }
+
+    #[test]
+    fn every_system_spawns_pieces_in_its_own_shape() {
+        let flat = vec![(-1,0), (0,0), (0,1), (1,0)];
+        let t_shapes = [vec![(-1,0), (0,-1), (0,0), (1,0)], flat.clone(), flat.clone(), flat];
+        for (system, shape) in SYSTEMS_UNDER_TEST.iter().zip(t_shapes.iter()) {
+            let mut cells = system.def(PieceType::T).cells(Rotation::Up).to_vec();
+            cells.sort_unstable();
+            assert_eq!(&cells, shape, "T in {:?}", system);
+
+            let manager = TetrisManager::with_config(
+                &GameConfig::default().rotation_system(*system).seed(1)
+            );
+            let piece = manager.current();
+            assert_eq!(piece.system(), *system);
+            assert_eq!(piece.rotation(), Rotation::Up);
+            // Above the field, then one row down right away
+            let bottom = piece.coordinates().iter().map(|(_,y)| *y).max();
+            assert_eq!(bottom, Option::Some(piece.def().spawn.1), "{:?}", system);
+        }
+    }
+
+    #[test]
+    fn ars_doesnt_kick_when_the_center_column_is_blocked() {
+        let mut field: GameField = EMPTY.parse().unwrap();
+        field.set_block(4, 2, Block::garbage());
+        let piece = Piece::new(PieceType::T, Color::GRAY, (4,3)).with_system(&Ars);
+        assert!(field.valid_piece(piece));
+        // The right kick has room, ARS just doesn't try it
+        assert!(field.valid_piece(piece.with_rotation(Rotation::Right).moved_by(1, 0)));
+        assert_eq!(rotate(&field, piece, Rotation::Right), Option::None);
+        assert!(rotate(&field, piece.with_system(&Srs).rotate_180(), Rotation::Right).is_some());
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use crate::tetris::color::Color;
+use crate::tetris::piece::{PieceType, PIECE_TYPES};
+
+/// Why a skin couldn't be loaded.
+#[derive(Debug)]
+pub enum SkinError {
+    /// The file couldn't be read.
+    IOError(std::io::Error),
+    /// A line that isn't `name = color`, with its line number.
+    InvalidLine(usize),
+    /// The name of an entry isn't a single letter or `garbage`.
+    UnknownPiece(String),
+    /// The color of an entry isn't `#rrggbb`.
+    InvalidColor(String),
+}
+
+impl std::error::Error for SkinError {}
+
+impl std::fmt::Display for SkinError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            SkinError::IOError(e) => write!(fmt, "{}", e),
+            SkinError::InvalidLine(line) => write!(fmt, "line {} is not 'name = color'", line),
+            SkinError::UnknownPiece(p) => write!(fmt, "unknown piece '{}'", p),
+            SkinError::InvalidColor(c) => write!(fmt, "invalid color '{}'", c),
+        }
+    }
+}
+
+/// Which color each piece type and garbage is drawn with. Pieces are
+/// matched by letter, a piece without an entry uses the color from its
+/// `PieceDef`.
+///
+/// Skin files have one `name = #rrggbb` line per entry, where the name is a
+/// piece letter or `garbage`. Entries that are left out keep their
+/// guideline color:
+///
+/// ```text
+/// T = #ff00ff
+/// garbage = #404040
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Skin {
+    pieces: BTreeMap<char, Color>,
+    garbage: Color,
+}
+
+impl Default for Skin {
+    fn default() -> Skin {
+        Skin::guideline()
+    }
+}
+
+/// Names of the built in skins, in the order frontends cycle through them.
+pub const PRESETS : [&str; 5] =
+    ["guideline", "deuteranopia", "protanopia", "tritanopia", "high-contrast"];
+
+impl Skin {
+    /// A built in skin by its name in `PRESETS`.
+    pub fn preset(name: &str) -> Option<Skin> {
+        match name {
+            "guideline" => Option::Some(Skin::guideline()),
+            "deuteranopia" => Option::Some(Skin::deuteranopia()),
+            "protanopia" => Option::Some(Skin::protanopia()),
+            "tritanopia" => Option::Some(Skin::tritanopia()),
+            "high-contrast" => Option::Some(Skin::high_contrast()),
+            _ => Option::None
+        }
+    }
+
+    /// The preset after `name` in `PRESETS`, wrapping around.
+    pub fn next_preset(name: &str) -> &'static str {
+        let i = PRESETS.iter().position(|p| *p == name).map_or(0, |i| i + 1);
+        PRESETS[i % PRESETS.len()]
+    }
+
+    // Colors for the seven tetrominoes in the order of `PIECE_TYPES`
+    fn from_colors(colors: [Color; 7], garbage: Color) -> Skin {
+        Skin {
+            pieces: PIECE_TYPES.iter().map(|p| p.letter()).zip(colors.iter().cloned()).collect(),
+            garbage,
+        }
+    }
+
+    /// Cyan I, blue J, orange L, yellow O, green S, purple T and red Z.
+    pub fn guideline() -> Skin {
+        Skin::from_colors(
+            [
+                Color::CYAN,
+                Color::BLUE,
+                Color::ORANGE,
+                Color::YELLOW,
+                Color::GREEN,
+                Color::PURPLE,
+                Color::RED,
+            ],
+            Color::GRAY,
+        )
+    }
+
+    // The Okabe-Ito palette, which stays apart for red-green color blindness
+    fn okabe_ito(z: Color, garbage: Color) -> Skin {
+        Skin::from_colors(
+            [
+                Color::rgb(86, 180, 233),
+                Color::rgb(0, 114, 178),
+                Color::rgb(230, 159, 0),
+                Color::rgb(240, 228, 66),
+                Color::rgb(0, 158, 115),
+                Color::rgb(204, 121, 167),
+                z,
+            ],
+            garbage,
+        )
+    }
+
+    /// The Okabe-Ito palette with an orange Z, for red-green color blindness.
+    pub fn deuteranopia() -> Skin {
+        Skin::okabe_ito(Color::rgb(213, 94, 0), Color::GRAY)
+    }
+
+    /// Like `deuteranopia`, but reds look dark without red cones so Z is
+    /// made light instead.
+    pub fn protanopia() -> Skin {
+        Skin::okabe_ito(Color::rgb(230, 230, 230), Color::rgb(90, 90, 90))
+    }
+
+    /// Avoids telling blues from greens and yellows from violets.
+    pub fn tritanopia() -> Skin {
+        Skin::from_colors(
+            [
+                Color::rgb(0, 187, 187),
+                Color::rgb(40, 40, 150),
+                Color::rgb(240, 96, 96),
+                Color::rgb(255, 192, 203),
+                Color::rgb(140, 70, 0),
+                Color::rgb(170, 0, 120),
+                Color::rgb(230, 0, 0),
+            ],
+            Color::GRAY,
+        )
+    }
+
+    /// Saturated colors that all stand out against a black background.
+    pub fn high_contrast() -> Skin {
+        Skin::from_colors(
+            [
+                Color::CYAN,
+                Color::rgb(80, 130, 255),
+                Color::ORANGE,
+                Color::YELLOW,
+                Color::GREEN,
+                Color::MAGENTA,
+                Color::RED,
+            ],
+            Color::WHITE,
+        )
+    }
+
+    /// The color `piece_type` is drawn in.
+    pub fn color_of(&self, piece_type: PieceType) -> Color {
+        self.pieces.get(&piece_type.letter()).cloned()
+            .unwrap_or(piece_type.def().color)
+    }
+
+    /// The color garbage is drawn in.
+    pub fn garbage(&self) -> Color {
+        self.garbage
+    }
+
+    /// Sets the color of every piece with the letter `letter`.
+    pub fn set_color(&mut self, letter: char, color: Color) {
+        self.pieces.insert(letter, color);
+    }
+
+    /// Sets the color garbage is drawn in.
+    pub fn set_garbage(&mut self, color: Color) {
+        self.garbage = color
+    }
+
+    /// Reads a skin in the file format above, starting from the guideline
+    /// colors.
+    pub fn parse(text: &str) -> Result<Skin, SkinError> {
+        let mut skin = Skin::guideline();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue
+            }
+            let split = line.find('=').ok_or(SkinError::InvalidLine(i + 1))?;
+            let (name, value) = (line[.. split].trim(), line[split + 1 ..].trim());
+            let color = Color::from_hex(value)
+                .ok_or_else(|| SkinError::InvalidColor(value.to_string()))?;
+            if name == "garbage" {
+                skin.garbage = color;
+                continue
+            }
+            let mut letters = name.chars();
+            let letter = match (letters.next(), letters.next()) {
+                (Option::Some(c), Option::None) if c.is_ascii_alphabetic() => {
+                    c.to_ascii_uppercase()
+                },
+                _ => return Result::Err(SkinError::UnknownPiece(name.to_string()))
+            };
+            skin.set_color(letter, color);
+        }
+        Result::Ok(skin)
+    }
+
+    /// Reads the skin file at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Skin, SkinError> {
+        let text = fs::read_to_string(path).map_err(SkinError::IOError)?;
+        Skin::parse(&text)
+    }
+}
+
+impl std::fmt::Display for Skin {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        for (letter, color) in self.pieces.iter() {
+            writeln!(fmt, "{} = {}", letter, color.to_hex())?;
+        }
+        writeln!(fmt, "garbage = {}", self.garbage.to_hex())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::pieceset::PieceSet;
+
+    #[test]
+    fn guideline_colors_follow_the_piece_type() {
+        let skin = Skin::guideline();
+        assert_eq!(skin.color_of(PieceType::I), Color::CYAN);
+        assert_eq!(skin.color_of(PieceType::J), Color::BLUE);
+        assert_eq!(skin.color_of(PieceType::L), Color::ORANGE);
+        assert_eq!(skin.color_of(PieceType::O), Color::YELLOW);
+        assert_eq!(skin.color_of(PieceType::S), Color::GREEN);
+        assert_eq!(skin.color_of(PieceType::T), Color::PURPLE);
+        assert_eq!(skin.color_of(PieceType::Z), Color::RED);
+        assert_eq!(skin.garbage(), Color::GRAY);
+        assert_eq!(Skin::default(), skin);
+    }
+
+    #[test]
+    fn pieces_without_a_skin_color_use_their_own() {
+        let set = PieceSet::parse("name: pair\npiece: D\ncolor: #ff0000\n@#\n").unwrap();
+        let pair = set.by_letter('D').unwrap();
+        assert_eq!(Skin::guideline().color_of(pair), Color::rgb(255, 0, 0));
+        assert_eq!(Skin::deuteranopia().color_of(pair), Color::rgb(255, 0, 0));
+
+        let mut skin = Skin::guideline();
+        skin.set_color('D', Color::WHITE);
+        assert_eq!(skin.color_of(pair), Color::WHITE);
+    }
+
+    #[test]
+    fn skin_files_override_the_guideline() {
+        let skin = Skin::parse("t = #ff00ff
+
+  garbage=#404040  
+D = #010203
+").unwrap();
+        assert_eq!(skin.color_of(PieceType::T), Color::MAGENTA);
+        assert_eq!(skin.color_of(PieceType::I), Skin::guideline().color_of(PieceType::I));
+        assert_eq!(skin.garbage(), Color::rgb(64, 64, 64));
+        assert_eq!(Skin::parse(&skin.to_string()).unwrap(), skin);
+    }
+
+    #[test]
+    fn bad_skin_lines_are_rejected() {
+        let error = |text: &str| Skin::parse(text).unwrap_err().to_string();
+        assert_eq!(error("T = #ff00ff
+T #00ff00
+"), "line 2 is not 'name = color'");
+        assert_eq!(error("? = #ff00ff"), "unknown piece '?'");
+        assert_eq!(error("TZ = #ff00ff"), "unknown piece 'TZ'");
+        assert_eq!(error(" = #ff00ff"), "unknown piece ''");
+        assert_eq!(error("T = magenta"), "invalid color 'magenta'");
+        assert_eq!(error("T = #+1+2+3"), "invalid color '#+1+2+3'");
+    }
+
+    #[test]
+    fn presets_cycle_in_order() {
+        for name in PRESETS.iter() {
+            assert!(Skin::preset(name).is_some(), "{}", name);
+        }
+        assert_eq!(Skin::preset("sepia"), Option::None);
+        assert_eq!(Skin::next_preset("guideline"), "deuteranopia");
+        assert_eq!(Skin::next_preset(PRESETS[PRESETS.len() - 1]), PRESETS[0]);
+        assert_eq!(Skin::next_preset("my-skin.txt"), PRESETS[0]);
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use crate::tetris::config::GameConfig;
+use crate::tetris::gamefield::{Block, GameField};
+use crate::tetris::notation::letter_color;
+use crate::tetris::piece::{Piece, PieceType};
+use crate::tetris::rotation::{self, RotationSystem, Srs};
+
+/// One step of a solution. `hold` means hold is pressed before the piece is
+/// placed, `piece` is where it ends up.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Placement {
+    /// Where the piece ends up.
+    pub piece: Piece,
+    /// Whether hold is pressed first.
+    pub hold: bool,
+}
+
+/// Why no perfect clear was found.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SolverError {
+    /// Every sequence was tried and none clears the field.
+    NoSolution,
+    /// The time budget ran out before a solution was found.
+    OutOfTime,
+    /// The stack is taller than the solver is allowed to search.
+    TooHigh,
+}
+
+impl std::error::Error for SolverError {}
+
+impl std::fmt::Display for SolverError {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        match self {
+            SolverError::NoSolution => write!(fmt, "no perfect clear"),
+            SolverError::OutOfTime => write!(fmt, "ran out of time"),
+            SolverError::TooHigh => write!(fmt, "stack too high"),
+        }
+    }
+}
+
+/// Searches for a piece sequence that clears the whole field.
+///
+/// Placements are everything the piece can reach from spawn by shifting,
+/// rotating and soft dropping, so tucks and spins are found too. Every block
+/// has to stay within the bottom `max_height` rows. Pieces turn by `system`
+/// and may have any number of blocks, as in custom piece sets.
+pub struct PcSolver {
+    /// How long the search may take.
+    pub budget: Duration,
+    /// Rows from the bottom blocks may be placed in.
+    pub max_height: i32,
+    /// How pieces turn.
+    pub system: &'static dyn RotationSystem,
+}
+
+impl Default for PcSolver {
+    fn default() -> PcSolver {
+        PcSolver {
+            budget: Duration::from_secs(1),
+            max_height: 6,
+            system: &Srs,
+        }
+    }
+}
+
+struct Search<'a> {
+    queue: &'a [PieceType],
+    system: &'static dyn RotationSystem,
+    // Every piece left has a multiple of this many blocks
+    unit: i32,
+    deadline: Instant,
+    seen: HashSet<(Vec<bool>, PieceType, Option<PieceType>, usize)>,
+    path: Vec<Placement>,
+}
+
+impl PcSolver {
+    /// A solver turning pieces the way games with `config` do.
+    pub fn for_config(config: &GameConfig) -> PcSolver {
+        PcSolver {
+            system: config.rotation_system,
+            .. Default::default()
+        }
+    }
+
+    /// The placements that clear the field, playing `current` and then the
+    /// pieces of `queue` in order with `hold` in the hold.
+    pub fn solve(
+        &self,
+        field: &GameField,
+        current: PieceType,
+        hold: Option<PieceType>,
+        queue: &[PieceType]
+    ) -> Result<Vec<Placement>, SolverError> {
+        let width = field.width();
+        let filled = field.get_blocks().len() as i32;
+        let stack = (0 .. field.height())
+            .find(|y| (0 .. width).any(|x| field.contains_node(x, *y)))
+            .map_or(0, |top| field.height() - top);
+        if stack > self.max_height {
+            return Result::Err(SolverError::TooHigh)
+        }
+
+        let sizes: Vec<i32> = std::iter::once(current).chain(hold).chain(queue.iter().cloned())
+            .map(|piece_type| piece_type.size() as i32)
+            .collect();
+        let blocks: i32 = sizes.iter().sum();
+        let unit = sizes.iter().fold(0, |a, b| gcd(a, *b));
+        let mut search = Search {
+            queue,
+            system: self.system,
+            unit,
+            deadline: Instant::now() + self.budget,
+            seen: HashSet::new(),
+            path: Vec::new(),
+        };
+        for height in stack.max(1) ..= self.max_height.min(field.height()) {
+            let empty = height * width - filled;
+            if empty % unit != 0 || empty > blocks {
+                continue
+            }
+            search.seen.clear();
+            if search.step(field, height, Option::Some(current), hold, 0)? {
+                return Result::Ok(search.path)
+            }
+        }
+        Result::Err(SolverError::NoSolution)
+    }
+}
+
+impl<'a> Search<'a> {
+    // Tries every way to place the next piece, with and without hold
+    fn step(
+        &mut self,
+        field: &GameField,
+        rows: i32,
+        current: Option<PieceType>,
+        hold: Option<PieceType>,
+        next: usize
+    ) -> Result<bool, SolverError> {
+        if Instant::now() > self.deadline {
+            return Result::Err(SolverError::OutOfTime)
+        }
+        let current = match current {
+            Option::Some(current) => current,
+            Option::None => return Result::Ok(false)
+        };
+        let key = (cells_of(field, rows), current, hold, next);
+        if !self.seen.insert(key) {
+            return Result::Ok(false)
+        }
+
+        let upcoming = self.queue.get(next).cloned();
+        let mut choices = vec![(current, false, hold, upcoming, next + 1)];
+        match hold {
+            Option::Some(held) if held != current => {
+                choices.push((held, true, Option::Some(current), upcoming, next + 1))
+            },
+            Option::None => {
+                if let Option::Some(swapped) = upcoming {
+                    let after = self.queue.get(next + 1).cloned();
+                    choices.push((swapped, true, Option::Some(current), after, next + 2))
+                }
+            },
+            _ => ()
+        }
+
+        for (piece_type, used_hold, hold, current, next) in choices {
+            for piece in placements(field, rows, piece_type, self.system) {
+                let mut placed = field.clone();
+                placed.insert_blocks(&piece.coordinates(), Block::of_piece(&piece));
+                let mut cleared = 0;
+                for y in field.height() - rows .. field.height() {
+                    if placed.is_row_full(y) {
+                        placed.delete_row(y);
+                        cleared += 1;
+                    }
+                }
+                self.path.push(Placement { piece, hold: used_hold });
+                if placed.is_empty() {
+                    return Result::Ok(true)
+                }
+                if regions_fillable(&placed, rows - cleared, self.unit)
+                    && self.step(&placed, rows - cleared, current, hold, next)? {
+                    return Result::Ok(true)
+                }
+                self.path.pop();
+            }
+        }
+        Result::Ok(false)
+    }
+}
+
+fn cells_of(field: &GameField, rows: i32) -> Vec<bool> {
+    let mut res = Vec::new();
+    for y in field.height() - rows .. field.height() {
+        for x in 0 .. field.width() {
+            res.push(field.contains_node(x, y));
+        }
+    }
+    res
+}
+
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+// Every enclosed area of empty cells has to be filled by whole pieces, so
+// its size is a multiple of `unit`
+fn regions_fillable(field: &GameField, rows: i32, unit: i32) -> bool {
+    let (width, top) = (field.width(), field.height() - rows);
+    let mut visited: HashSet<(i32,i32)> = HashSet::new();
+    for y in top .. field.height() {
+        for x in 0 .. width {
+            if field.contains_node(x, y) || visited.contains(&(x, y)) {
+                continue
+            }
+            let mut size = 0;
+            let mut stack = vec![(x, y)];
+            visited.insert((x, y));
+            while let Option::Some((cx, cy)) = stack.pop() {
+                size += 1;
+                for (nx, ny) in [(cx - 1, cy), (cx + 1, cy), (cx, cy - 1), (cx, cy + 1)].iter() {
+                    let inside = *nx >= 0 && *nx < width
+                        && *ny >= top && *ny < field.height();
+                    if inside && !field.contains_node(*nx, *ny)
+                        && visited.insert((*nx, *ny)) {
+                        stack.push((*nx, *ny));
+                    }
+                }
+            }
+            if size % unit != 0 {
+                return false
+            }
+        }
+    }
+    true
+}
+
+/// Every distinct resting place a piece turning by `system` can reach,
+/// keeping every block within the bottom `rows` rows.
+pub fn placements(
+    field: &GameField,
+    rows: i32,
+    piece_type: PieceType,
+    system: &'static dyn RotationSystem
+) -> Vec<Piece> {
+    // Start a few rows above the area, there is nothing up there to collide with
+    let start_y = (field.height() - rows - 4).max(0);
+    let start = Piece::new(
+        piece_type,
+        letter_color(piece_type),
+        (field.width() / 2, start_y)
+    ).with_system(system);
+    let mut found: Vec<Piece> = Vec::new();
+    let mut found_cells: HashSet<Vec<(i32,i32)>> = HashSet::new();
+    let mut visited = HashSet::new();
+    let mut open = VecDeque::new();
+    if !field.valid_piece(start) {
+        return found
+    }
+    visited.insert((start.rotation(), start.position()));
+    open.push_back(start);
+    while let Option::Some(piece) = open.pop_front() {
+        let down = piece.move_down();
+        if !field.valid_piece(down) {
+            let mut cells = piece.coordinates().to_vec();
+            cells.sort_unstable();
+            let inside = cells.iter().all(|(_,y)| *y >= field.height() - rows);
+            if inside && found_cells.insert(cells) {
+                found.push(piece);
+            }
+        }
+        let moves = [
+            Option::Some(down),
+            Option::Some(piece.move_left()),
+            Option::Some(piece.move_right()),
+            rotation::rotate(field, piece, piece.rotation().rotate_right()),
+            rotation::rotate(field, piece, piece.rotation().rotate_left()),
+            rotation::rotate(field, piece, piece.rotation().rotate_180()),
+        ];
+        for moved in moves.iter().flatten() {
+            if field.valid_piece(*moved)
+                && visited.insert((moved.rotation(), moved.position())) {
+                open.push_back(*moved);
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::pieceset::PieceSet;
+    use crate::tetris::rotation::Ars;
+
+    fn field(text: &str) -> GameField {
+        text.parse().unwrap()
+    }
+
+    // Plays the solution out and checks it empties the field
+    fn clears(field: &GameField, solution: &[Placement]) -> bool {
+        let mut field = field.clone();
+        for placement in solution {
+            let piece = placement.piece;
+            if !field.valid_piece(piece) || field.valid_piece(piece.move_down()) {
+                return false
+            }
+            field.insert_blocks(&piece.coordinates(), Block::of_piece(&piece));
+            for y in 0 .. field.height() {
+                if field.is_row_full(y) {
+                    field.delete_row(y);
+                }
+            }
+        }
+        field.is_empty()
+    }
+
+    #[test]
+    fn finds_a_single_piece_clear() {
+        let board = field("..........\n..........\n..........\n######....");
+        let solution = PcSolver::default().solve(&board, PieceType::I, Option::None, &[]).unwrap();
+        assert_eq!(solution.len(), 1);
+        assert!(!solution[0].hold);
+        assert!(clears(&board, &solution));
+    }
+
+    #[test]
+    fn uses_hold_when_the_current_piece_doesnt_fit() {
+        let board = field("..........\n..........\n..........\n######....");
+        let solution = PcSolver::default()
+            .solve(&board, PieceType::O, Option::Some(PieceType::I), &[])
+            .unwrap();
+        assert_eq!(solution.len(), 1);
+        assert!(solution[0].hold);
+        assert_eq!(solution[0].piece.piece_type(), PieceType::I);
+    }
+
+    #[test]
+    fn two_row_clear_with_a_queue() {
+        let board = field("..........\n..........\n####....##\n####....##");
+        let solution = PcSolver::default()
+            .solve(&board, PieceType::O, Option::None, &[PieceType::O])
+            .unwrap();
+        assert_eq!(solution.len(), 2);
+        assert!(clears(&board, &solution));
+    }
+
+    #[test]
+    fn rejects_impossible_and_tall_stacks() {
+        let board = field("..........\n#######.##");
+        assert_eq!(
+            PcSolver::default().solve(&board, PieceType::I, Option::None, &[]),
+            Result::Err(SolverError::NoSolution)
+        );
+        let tall = field(&"#########.\n".repeat(8));
+        assert_eq!(
+            PcSolver::default().solve(&tall, PieceType::I, Option::None, &[]),
+            Result::Err(SolverError::TooHigh)
+        );
+    }
+
+    #[test]
+    fn places_with_the_configured_rotation_system() {
+        let config = GameConfig::default().rotation_system(&Ars);
+        let solver = PcSolver::for_config(&config);
+        let board = field("..........\n..........\n..........\n######....");
+        let solution = solver.solve(&board, PieceType::I, Option::None, &[]).unwrap();
+        assert_eq!(solution[0].piece.system().name(), "ars");
+        assert!(clears(&board, &solution));
+    }
+
+    #[test]
+    fn solves_with_pieces_of_three_blocks() {
+        let set = PieceSet::trominoes();
+        let tromino = set.by_letter('I').unwrap();
+        let board = field("..........\n..........\n#######...");
+        let solution = PcSolver::default().solve(&board, tromino, Option::None, &[]).unwrap();
+        assert_eq!(solution.len(), 1);
+        assert!(clears(&board, &solution));
+    }
+}
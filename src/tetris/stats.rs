@@ -0,0 +1,336 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use crate::tetris::event::{Event, TSpin};
+
+// Garbage sent for a clear of 0 to 4 rows, and for a full T-spin
+const CLEAR_ATTACK : [u32; 5] = [0, 0, 1, 2, 4];
+const TSPIN_ATTACK : [u32; 4] = [0, 2, 4, 6];
+// Extra garbage by combo length, the last entry repeats
+const COMBO_ATTACK : [u32; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+const PERFECT_CLEAR_ATTACK : u32 = 10;
+
+/// How many locks cleared rows, by kind of clear.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct ClearHistogram {
+    /// Locks that cleared one row.
+    pub singles: u32,
+    /// Locks that cleared two rows.
+    pub doubles: u32,
+    /// Locks that cleared three rows.
+    pub triples: u32,
+    /// Locks that cleared four rows.
+    pub tetrises: u32,
+    /// Mini T-spins that cleared rows.
+    pub tspin_minis: u32,
+    /// T-spins that cleared one row.
+    pub tspin_singles: u32,
+    /// T-spins that cleared two rows.
+    pub tspin_doubles: u32,
+    /// T-spins that cleared three rows.
+    pub tspin_triples: u32,
+    /// Clears that left the field empty, also counted by their kind.
+    pub perfect_clears: u32,
+}
+
+/// A snapshot of `Stats` with the rates worked out, ready to serialize.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StatsReport {
+    /// Seconds played.
+    pub time: f64,
+    /// Pieces locked.
+    pub pieces: u32,
+    /// Inputs pressed.
+    pub keys: u32,
+    /// Rows cleared.
+    pub lines: u32,
+    /// Garbage the clears would send in a versus game.
+    pub attack: u32,
+    /// Pieces per second.
+    pub pps: f64,
+    /// Inputs per piece.
+    pub kpp: f64,
+    /// Attack per minute.
+    pub apm: f64,
+    /// Longest combo, clearing locks in a row.
+    pub max_combo: u32,
+    /// Longest back to back chain of difficult clears.
+    pub max_b2b: u32,
+    /// Clears by kind.
+    pub clears: ClearHistogram,
+}
+
+impl StatsReport {
+    /// The report as pretty printed JSON.
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+// What one lock did, collected from the events it produced
+#[derive(Default)]
+struct Lock {
+    lines: u32,
+    tspin: Option<TSpin>,
+    perfect_clear: bool,
+}
+
+/// Collects statistics for one game from the manager's events.
+///
+/// Hand it every batch from `TetrisManager::drain_events`. The clock starts
+/// when the collector is made and stops once the game ends.
+pub struct Stats {
+    started: Instant,
+    finished: Option<Duration>,
+    pieces: u32,
+    keys: u32,
+    lines: u32,
+    attack: u32,
+    // Clearing locks in a row, 0 when the last lock cleared nothing
+    combo: u32,
+    max_combo: u32,
+    // Tetrises and T-spin clears in a row without a plain clear between them
+    b2b: u32,
+    max_b2b: u32,
+    clears: ClearHistogram,
+    pending: Option<Lock>,
+}
+
+impl Default for Stats {
+    fn default() -> Stats {
+        Stats::new()
+    }
+}
+
+impl Stats {
+    /// A collector with the clock started now.
+    pub fn new() -> Stats {
+        Stats {
+            started: Instant::now(),
+            finished: Option::None,
+            pieces: 0,
+            keys: 0,
+            lines: 0,
+            attack: 0,
+            combo: 0,
+            max_combo: 0,
+            b2b: 0,
+            max_b2b: 0,
+            clears: ClearHistogram::default(),
+            pending: Option::None,
+        }
+    }
+
+    /// Counts the events of one `TetrisManager::drain_events` batch.
+    pub fn observe(&mut self, events: &[Event]) {
+        for event in events {
+            match event {
+                Event::Input(_) => self.keys += 1,
+                Event::Locked(_) => {
+                    self.finish_lock();
+                    self.pieces += 1;
+                    self.pending = Option::Some(Lock::default());
+                },
+                Event::LinesCleared(n) => {
+                    self.pending.get_or_insert_with(Lock::default).lines += *n
+                },
+                Event::ChainEnded(_) => self.finish_lock(),
+                Event::TSpin(kind, _) => {
+                    self.pending.get_or_insert_with(Lock::default).tspin =
+                        Option::Some(*kind)
+                },
+                Event::PerfectClear => {
+                    self.pending.get_or_insert_with(Lock::default).perfect_clear = true
+                },
+                Event::GameOver | Event::Finished | Event::OutOfPieces
+                    if self.finished.is_none() => {
+                        self.finish_lock();
+                        self.finished = Option::Some(self.started.elapsed());
+                    },
+                _ => ()
+            }
+        }
+        // A lock that cleared nothing is done, one that cleared rows waits
+        // for `ChainEnded`, which comes in a later batch after a clear delay
+        if self.pending.as_ref().is_some_and(|lock| lock.lines == 0) {
+            self.finish_lock();
+        }
+    }
+
+    fn finish_lock(&mut self) {
+        let lock = match self.pending.take() {
+            Option::Some(lock) => lock,
+            Option::None => return
+        };
+        if lock.lines == 0 {
+            self.combo = 0;
+            return
+        }
+
+        let lines = lock.lines.min(4) as usize;
+        let difficult = lines == 4 || lock.tspin.is_some();
+        let mut attack = match lock.tspin {
+            Option::Some(TSpin::Full) => TSPIN_ATTACK[lines.min(3)],
+            Option::Some(TSpin::Mini) => lines as u32 - 1,
+            Option::None => CLEAR_ATTACK[lines],
+        };
+        if difficult {
+            if self.b2b > 0 {
+                attack += 1;
+            }
+            self.b2b += 1;
+        } else {
+            self.b2b = 0;
+        }
+        attack += COMBO_ATTACK[(self.combo as usize).min(COMBO_ATTACK.len() - 1)];
+        if lock.perfect_clear {
+            attack += PERFECT_CLEAR_ATTACK;
+            self.clears.perfect_clears += 1;
+        }
+        self.combo += 1;
+
+        let bucket = match (lock.tspin, lines) {
+            (Option::Some(TSpin::Mini), _) => &mut self.clears.tspin_minis,
+            (Option::Some(TSpin::Full), 1) => &mut self.clears.tspin_singles,
+            (Option::Some(TSpin::Full), 2) => &mut self.clears.tspin_doubles,
+            (Option::Some(TSpin::Full), _) => &mut self.clears.tspin_triples,
+            (Option::None, 1) => &mut self.clears.singles,
+            (Option::None, 2) => &mut self.clears.doubles,
+            (Option::None, 3) => &mut self.clears.triples,
+            (Option::None, _) => &mut self.clears.tetrises,
+        };
+        *bucket += 1;
+        self.lines += lock.lines;
+        self.attack += attack;
+        self.max_combo = self.max_combo.max(self.combo - 1);
+        self.max_b2b = self.max_b2b.max(self.b2b.saturating_sub(1));
+    }
+
+    /// Time played, frozen once the game is over.
+    pub fn elapsed(&self) -> Duration {
+        self.finished.unwrap_or_else(|| self.started.elapsed())
+    }
+
+    /// Whether the game ended, which stops the clock.
+    pub fn is_finished(&self) -> bool {
+        self.finished.is_some()
+    }
+
+    /// Current combo, 0 for the first clear in a row.
+    pub fn combo(&self) -> u32 {
+        self.combo.saturating_sub(1)
+    }
+
+    /// Current back to back chain, 0 for the first difficult clear.
+    pub fn b2b(&self) -> u32 {
+        self.b2b.saturating_sub(1)
+    }
+
+    /// The statistics so far with the rates worked out.
+    pub fn report(&self) -> StatsReport {
+        let time = self.elapsed().as_millis() as f64 / 1000.;
+        let per_second = |n: u32| if time > 0. { n as f64 / time } else { 0. };
+        StatsReport {
+            time,
+            pieces: self.pieces,
+            keys: self.keys,
+            lines: self.lines,
+            attack: self.attack,
+            pps: per_second(self.pieces),
+            kpp: if self.pieces > 0 { self.keys as f64 / self.pieces as f64 } else { 0. },
+            apm: per_second(self.attack) * 60.,
+            max_combo: self.max_combo,
+            max_b2b: self.max_b2b,
+            clears: self.clears,
+        }
+    }
+
+    /// A file in `dir` named after the current time, for saving a session.
+    pub fn session_file<P: AsRef<Path>>(dir: P) -> PathBuf {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        dir.as_ref().join(format!("{}.json", now))
+    }
+
+    /// Writes the report as JSON, creating missing parent directories.
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let path = path.as_ref();
+        if let Option::Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = self.report().to_json()
+            .map_err(std::io::Error::other)?;
+        fs::write(path, json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetris::piece::PieceType;
+
+    fn clear(piece_type: PieceType, lines: u32) -> Vec<Event> {
+        vec![Event::Locked(piece_type), Event::LinesCleared(lines), Event::ChainEnded(1)]
+    }
+
+    #[test]
+    fn tetrises_in_a_row_are_back_to_back() {
+        let mut stats = Stats::new();
+        stats.observe(&clear(PieceType::I, 4));
+        stats.observe(&clear(PieceType::I, 4));
+        let report = stats.report();
+        assert_eq!((report.pieces, report.lines, report.attack), (2, 8, 9));
+        assert_eq!(report.clears.tetrises, 2);
+        assert_eq!(stats.b2b(), 1);
+    }
+
+    #[test]
+    fn a_lock_without_a_clear_ends_the_combo() {
+        let mut stats = Stats::new();
+        stats.observe(&clear(PieceType::L, 1));
+        stats.observe(&clear(PieceType::J, 1));
+        assert_eq!(stats.combo(), 1);
+        stats.observe(&[Event::Locked(PieceType::O)]);
+        assert_eq!(stats.combo(), 0);
+        let report = stats.report();
+        assert_eq!((report.pieces, report.max_combo), (3, 1));
+        assert_eq!(report.clears.singles, 2);
+    }
+
+    #[test]
+    fn tspins_are_counted_by_rows() {
+        let mut stats = Stats::new();
+        stats.observe(&[
+            Event::Locked(PieceType::T),
+            Event::TSpin(TSpin::Full, 2),
+            Event::LinesCleared(2),
+            Event::ChainEnded(1),
+        ]);
+        let report = stats.report();
+        assert_eq!(report.clears.tspin_doubles, 1);
+        assert_eq!(report.attack, 4);
+    }
+
+    #[test]
+    fn chain_links_count_towards_the_lock_that_started_them() {
+        let mut stats = Stats::new();
+        stats.observe(&[Event::Locked(PieceType::S), Event::LinesCleared(2)]);
+        stats.observe(&[]);
+        stats.observe(&[Event::Chain(2), Event::LinesCleared(1), Event::ChainEnded(2)]);
+        let report = stats.report();
+        assert_eq!((report.pieces, report.lines), (1, 3));
+        assert_eq!(report.clears.triples, 1);
+        assert_eq!(stats.combo(), 0);
+    }
+
+    #[test]
+    fn game_over_finishes_the_last_lock() {
+        let mut stats = Stats::new();
+        stats.observe(&[Event::Locked(PieceType::I), Event::LinesCleared(1)]);
+        stats.observe(&[Event::GameOver]);
+        assert!(stats.is_finished());
+        assert_eq!(stats.report().lines, 1);
+    }
+}
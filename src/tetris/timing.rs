@@ -0,0 +1,127 @@
+/// Gravity from which pieces drop to the floor the moment they spawn or
+/// move, however tall the field is.
+pub const SONIC_GRAVITY : f32 = 20.;
+
+/// Speeds and delays of a game at one moment, delays are in frames.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Timing {
+    /// Rows the active piece falls per frame, 1/60 is a row a second.
+    pub gravity: f32,
+    /// Entry delay between a lock and the next spawn.
+    pub are: u32,
+    /// Entry delay after a lock that cleared rows, in place of `are`.
+    pub line_are: u32,
+    /// Frames full rows stay on the field before they are removed, before
+    /// `line_are` starts. Every link of a chain waits again.
+    pub line_clear: u32,
+    /// Frames a direction is held before the piece shifts on its own.
+    pub das: u32,
+    /// Frames a piece rests on the stack before it locks, restarted
+    /// whenever it falls a row.
+    pub lock_delay: u32,
+}
+
+impl Default for Timing {
+    fn default() -> Timing {
+        Timing {
+            gravity: 1. / 60.,
+            are: 0,
+            line_are: 0,
+            line_clear: 0,
+            das: 10,
+            lock_delay: 30,
+        }
+    }
+}
+
+// Gravity of master mode in 256ths of a row per frame, by the level it
+// starts at. It drops back at 200 and reaches 20G at 500.
+const MASTER_GRAVITY : [(u32, u32); 30] = [
+    (0, 4), (30, 6), (35, 8), (40, 10), (50, 12), (60, 16), (70, 32),
+    (80, 48), (90, 64), (100, 80), (120, 96), (140, 112), (160, 128),
+    (170, 144), (200, 4), (220, 32), (230, 64), (233, 96), (236, 128),
+    (239, 160), (243, 192), (247, 224), (251, 256), (300, 512), (330, 768),
+    (360, 1024), (400, 1280), (420, 1024), (450, 768), (500, 5120),
+];
+
+// Delays of master mode by the level their section starts at: ARE, line
+// ARE, DAS, lock delay and line clear
+const MASTER_DELAYS : [(u32, [u32; 5]); 6] = [
+    (0, [25, 25, 14, 30, 40]),
+    (500, [25, 25, 8, 30, 25]),
+    (600, [25, 16, 8, 30, 16]),
+    (700, [16, 12, 8, 30, 12]),
+    (800, [12, 6, 8, 30, 6]),
+    (900, [12, 6, 6, 17, 6]),
+];
+
+// The entry of a table by level that applies at `level`
+fn section<T: Copy>(table: &[(u32, T)], level: u32) -> T {
+    table.iter().rev()
+        .find(|(start, _)| *start <= level)
+        .map_or(table[0].1, |(_, value)| *value)
+}
+
+impl Timing {
+    /// Timing of master mode at `level`, after TGM2's tables.
+    pub fn master(level: u32) -> Timing {
+        let [are, line_are, das, lock_delay, line_clear] = section(&MASTER_DELAYS, level);
+        Timing {
+            gravity: section(&MASTER_GRAVITY, level) as f32 / 256.,
+            are,
+            line_are,
+            line_clear,
+            das,
+            lock_delay,
+        }
+    }
+
+    /// Entry delay after a lock that cleared `cleared` rows, counted from
+    /// when the rows are gone.
+    pub fn entry_delay(&self, cleared: u32) -> u32 {
+        if cleared > 0 {
+            self.line_are
+        } else {
+            self.are
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn master_gravity_follows_the_level_table() {
+        assert_eq!(Timing::master(0).gravity, 4. / 256.);
+        assert_eq!(Timing::master(29).gravity, 4. / 256.);
+        assert_eq!(Timing::master(30).gravity, 6. / 256.);
+        assert_eq!(Timing::master(199).gravity, 144. / 256.);
+        assert_eq!(Timing::master(200).gravity, 4. / 256.);
+        assert_eq!(Timing::master(251).gravity, 1.);
+        assert!(Timing::master(499).gravity < SONIC_GRAVITY);
+        assert_eq!(Timing::master(500).gravity, SONIC_GRAVITY);
+        assert_eq!(Timing::master(999).gravity, SONIC_GRAVITY);
+    }
+
+    #[test]
+    fn master_delays_change_by_section() {
+        let delays = |level| {
+            let t = Timing::master(level);
+            (t.are, t.line_are, t.das, t.lock_delay, t.line_clear)
+        };
+        assert_eq!(delays(0), (25, 25, 14, 30, 40));
+        assert_eq!(delays(499), (25, 25, 14, 30, 40));
+        assert_eq!(delays(500), (25, 25, 8, 30, 25));
+        assert_eq!(delays(799), (16, 12, 8, 30, 12));
+        assert_eq!(delays(950), (12, 6, 6, 17, 6));
+    }
+
+    #[test]
+    fn entry_delay_depends_on_the_clear() {
+        let timing = Timing { are: 20, line_are: 7, .. Timing::default() };
+        assert_eq!(timing.entry_delay(0), 20);
+        assert_eq!(timing.entry_delay(1), 7);
+        assert_eq!(timing.entry_delay(4), 7);
+    }
+}
@@ -0,0 +1,73 @@
+use termion::color;
+use tetris::Color;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorMode {
+    TrueColor,
+    Ansi256,
+}
+
+fn to_byte(v: f32) -> u8 {
+    (v.clamp(0., 1.) * 255.).round() as u8
+}
+
+// The 256 color palette has a 6x6x6 color cube starting at index 16
+fn to_cube(v: f32) -> u8 {
+    (v.clamp(0., 1.) * 5.).round() as u8
+}
+
+impl ColorMode {
+    // Most terminals that support 24 bit color advertise it through COLORTERM
+    pub fn detect() -> ColorMode {
+        match std::env::var("COLORTERM") {
+            Result::Ok(ref v) if v == "truecolor" || v == "24bit" => {
+                ColorMode::TrueColor
+            },
+            _ => ColorMode::Ansi256
+        }
+    }
+
+    pub fn fg(self, c: &Color) -> String {
+        let (r,g,b): (f32,f32,f32) = c.into();
+        match self {
+            ColorMode::TrueColor => format!(
+                "{}", color::Fg(color::Rgb(to_byte(r), to_byte(g), to_byte(b)))
+            ),
+            ColorMode::Ansi256 => format!(
+                "{}", color::Fg(color::AnsiValue::rgb(to_cube(r), to_cube(g), to_cube(b)))
+            ),
+        }
+    }
+
+    pub fn bg(self, c: &Color) -> String {
+        let (r,g,b): (f32,f32,f32) = c.into();
+        match self {
+            ColorMode::TrueColor => format!(
+                "{}", color::Bg(color::Rgb(to_byte(r), to_byte(g), to_byte(b)))
+            ),
+            ColorMode::Ansi256 => format!(
+                "{}", color::Bg(color::AnsiValue::rgb(to_cube(r), to_cube(g), to_cube(b)))
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn true_color_writes_rgb_escapes() {
+        let orange = Color::rgb(255, 128, 0);
+        assert_eq!(ColorMode::TrueColor.fg(&orange), "\x1b[38;2;255;128;0m");
+        assert_eq!(ColorMode::TrueColor.bg(&orange), "\x1b[48;2;255;128;0m");
+    }
+
+    #[test]
+    fn ansi_256_rounds_into_the_color_cube() {
+        // 16 + 36 * 5 + 6 * 3
+        assert_eq!(ColorMode::Ansi256.fg(&Color::rgb(255, 128, 0)), "\x1b[38;5;214m");
+        assert_eq!(ColorMode::Ansi256.bg(&Color::WHITE), "\x1b[48;5;231m");
+        assert_eq!(ColorMode::Ansi256.bg(&Color::rgb(0, 0, 0)), "\x1b[48;5;16m");
+    }
+}
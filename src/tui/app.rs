@@ -0,0 +1,396 @@
+use std::io::Write;
+use std::fmt::Write as FmtWrite;
+use std::time::{Duration, Instant};
+use termion::{clear, cursor, style};
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::IntoRawMode;
+use tetris::{Action, BlockKind, Color, GameConfig, Piece, Skin, TetrisManager, FRAME};
+use tetris::{Puzzle, PuzzleProgress, PuzzleState, Record, Records, Replay, Stats};
+use crate::tui::ansi::ColorMode;
+
+const BOARD_WIDTH : i32 = 10;
+const BOARD_HEIGHT : i32 = 20;
+
+// Terminal position of the top left field cell, terminal coordinates start at 1
+const ORIGIN : (u16, u16) = (2, 2);
+
+// Terminals don't report key releases, so there is no DAS charge here.
+// Holding a direction shifts at the terminal's key repeat rate, Home and End
+// shift to the wall
+fn action_for_key(key: Key) -> Option<Action> {
+    match key {
+        Key::Left | Key::Char('a') => Option::Some(Action::MoveLeft),
+        Key::Right | Key::Char('d') => Option::Some(Action::MoveRight),
+        Key::Home | Key::Char('A') => Option::Some(Action::DasLeft),
+        Key::End | Key::Char('D') => Option::Some(Action::DasRight),
+        Key::Down | Key::Char('s') => Option::Some(Action::SoftDrop),
+        Key::Up | Key::Char('w') | Key::Char('x') => {
+            Option::Some(Action::RotateRight)
+        },
+        Key::Char('z') => Option::Some(Action::RotateLeft),
+        Key::Char('v') => Option::Some(Action::Rotate180),
+        Key::Char(' ') => Option::Some(Action::HardDrop),
+        Key::Char('c') => Option::Some(Action::Hold),
+        _ => Option::None
+    }
+}
+
+fn cell_position(x: i32, y: i32) -> cursor::Goto {
+    cursor::Goto(ORIGIN.0 + 2 * x as u16, ORIGIN.1 + y as u16)
+}
+
+// Draws a piece in its spawn orientation with its top left corner at (col, row),
+// pieces up to five wide and three tall fit
+fn draw_mini_piece(
+    out: &mut String,
+    mode: ColorMode,
+    piece: &Piece,
+    (col, row): (u16, u16)
+) {
+    for y in 0 .. 3 {
+        write!(out, "{}          ", cursor::Goto(col, row + y)).unwrap();
+    }
+    let coords = piece.coordinates();
+    let (min_x, min_y) = match (
+        coords.iter().map(|(x,_)| *x).min(),
+        coords.iter().map(|(_,y)| *y).min()
+    ) {
+        (Option::Some(min_x), Option::Some(min_y)) => (min_x, min_y),
+        _ => return
+    };
+    for (x,y) in coords.iter() {
+        let goto = cursor::Goto(
+            col + 2 * (x - min_x) as u16,
+            row + (y - min_y) as u16
+        );
+        write!(out, "{}{}  {}", goto, mode.bg(&piece.color()), style::Reset)
+            .unwrap();
+    }
+}
+
+// With `patterns` every block shows its piece letter, so pieces can be told
+// apart without color
+fn draw(manager: &TetrisManager, mode: ColorMode, patterns: bool) -> String {
+    let mut out = String::new();
+    let width = manager.num_columns();
+    let height = manager.num_rows();
+
+    for y in 0 .. height {
+        write!(out, "{}│", cursor::Goto(ORIGIN.0 - 1, ORIGIN.1 + y as u16))
+            .unwrap();
+        for _ in 0 .. width {
+            write!(out, " .").unwrap();
+        }
+        write!(out, "│").unwrap();
+    }
+    write!(out, "{}└", cursor::Goto(ORIGIN.0 - 1, ORIGIN.1 + height as u16))
+        .unwrap();
+    for _ in 0 .. width {
+        write!(out, "──").unwrap();
+    }
+    write!(out, "┘").unwrap();
+
+    let ghost_color = manager.current().color();
+    for (x,y) in manager.ghost().iter().filter(|(_,y)| *y >= 0) {
+        write!(out, "{}{}[]{}", cell_position(*x,*y), mode.fg(&ghost_color), style::Reset)
+            .unwrap();
+    }
+    let clearing = manager.clearing_rows();
+    for ((x,y), block) in manager.visible_blocks().iter() {
+        let glyph = match block.kind {
+            BlockKind::Piece(piece_type) if patterns => piece_type.letter(),
+            _ => ' ',
+        };
+        // Rows waiting out the line clear delay flash white
+        let color = if clearing.contains(y) { Color::WHITE } else { block.color };
+        write!(
+            out, "{}{}{}{} {}",
+            cell_position(*x,*y), mode.bg(&color), mode.fg(&Color::rgb(0, 0, 0)),
+            glyph, style::Reset
+        ).unwrap();
+    }
+
+    let panel = ORIGIN.0 + 2 * width as u16 + 3;
+    write!(out, "{}Hold", cursor::Goto(panel, ORIGIN.1)).unwrap();
+    if let Option::Some(piece) = manager.held() {
+        draw_mini_piece(&mut out, mode, &piece, (panel, ORIGIN.1 + 1))
+    };
+
+    let stats = ORIGIN.1 + 4;
+    write!(out, "{}Score {:>8}", cursor::Goto(panel, stats), manager.score())
+        .unwrap();
+    write!(out, "{}Lines {:>8}", cursor::Goto(panel, stats + 1), manager.lines())
+        .unwrap();
+    write!(out, "{}Level {:>8}", cursor::Goto(panel, stats + 2), manager.level())
+        .unwrap();
+    let finesse = manager.finesse();
+    write!(out, "{}Faults {:>7}", cursor::Goto(panel, stats + 3), finesse.faults)
+        .unwrap();
+    write!(
+        out, "{}Finesse {:>5.0}%",
+        cursor::Goto(panel, stats + 4),
+        finesse.accuracy() * 100.
+    ).unwrap();
+    if manager.is_game_over() {
+        write!(out, "{}GAME OVER", cursor::Goto(panel, stats + 6)).unwrap();
+        write!(out, "{}q to quit", cursor::Goto(panel, stats + 7)).unwrap();
+    }
+
+    let next = panel + 16;
+    write!(out, "{}Next", cursor::Goto(next, ORIGIN.1)).unwrap();
+    for (i, piece) in manager.preview().iter().enumerate() {
+        let row = ORIGIN.1 + 1 + 3 * i as u16;
+        draw_mini_piece(&mut out, mode, piece, (next, row));
+    }
+    out
+}
+
+// Live statistics below the score panel
+fn draw_stats(manager: &TetrisManager, stats: &Stats) -> String {
+    let panel = ORIGIN.0 + 2 * manager.num_columns() as u16 + 3;
+    let row = ORIGIN.1 + 13;
+    let report = stats.report();
+    let time = stats.elapsed();
+    let lines = [
+        format!(
+            "Time {:>6}:{:02}.{:02}",
+            time.as_secs() / 60, time.as_secs() % 60, time.subsec_millis() / 10
+        ),
+        format!("PPS {:>10.2}", report.pps),
+        format!("APM {:>10.1}", report.apm),
+        format!("KPP {:>10.2}", report.kpp),
+        format!("Combo {:>8}", stats.combo()),
+        format!("B2B {:>10}", stats.b2b()),
+    ];
+    let mut out = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        write!(out, "{}{}", cursor::Goto(panel, row + i as u16), line).unwrap();
+    }
+    out
+}
+
+// Saves the result of a finished game with its replay, returns the line to
+// show for it
+fn record_result(
+    records: &mut Records,
+    config: &GameConfig,
+    manager: &TetrisManager,
+    replay: &Replay,
+    stats: &Stats
+) -> std::io::Result<String> {
+    let record = match Record::of_game(config, manager, stats.elapsed()) {
+        Option::Some(record) => record,
+        Option::None => return Result::Ok(String::new())
+    };
+    let mode = config.mode;
+    if records.add_with_replay(record, replay)? {
+        Result::Ok(String::from("NEW PB!"))
+    } else {
+        let best = records.personal_best(mode, config.config_hash(), &config.modifiers)
+            .map_or(0, |r| r.value);
+        Result::Ok(format!("PB {:>11}", mode.format_result(best)))
+    }
+}
+
+/// Plays a game with `config` on the terminal sized board, results are kept
+/// in `records.json` and their replays in `replays`.
+pub fn run(config: GameConfig) -> std::io::Result<()> {
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock().into_raw_mode()?;
+    let mut keys = termion::async_stdin().keys();
+    let mode = ColorMode::detect();
+
+    let game_mode = config.mode;
+    let config = config.width(BOARD_WIDTH).height(BOARD_HEIGHT);
+    let mut records = Records::load("records.json")?;
+    let mut result = String::new();
+    let mut manager = TetrisManager::with_config(&config);
+    let mut replay = Replay::new(&config, &manager);
+    let mut stats = Stats::new();
+    // p toggles letters on the blocks, k cycles through the built in skins,
+    // u and y undo and redo placements in practice mode
+    let mut patterns = false;
+    let mut skin_name = "guideline";
+    let mut last_frame = Instant::now();
+
+    write!(stdout, "{}{}", clear::All, cursor::Hide)?;
+    'game: loop {
+        for key in keys.by_ref() {
+            match key? {
+                Key::Char('q') | Key::Esc | Key::Ctrl('c') => break 'game,
+                Key::Char('p') => patterns = !patterns,
+                Key::Char('k') => {
+                    skin_name = Skin::next_preset(skin_name);
+                    manager.set_skin(Skin::preset(skin_name).unwrap());
+                },
+                Key::Char('u') => { manager.undo(); },
+                Key::Char('y') => { manager.redo(); },
+                key => if let Option::Some(action) = action_for_key(key) {
+                    replay.apply(&mut manager, action)
+                }
+            }
+        }
+
+        while last_frame.elapsed() >= FRAME {
+            replay.frame(&mut manager);
+            last_frame += FRAME;
+        }
+        if game_mode.time_limit().is_some_and(|limit| stats.elapsed() >= limit) {
+            replay.finish(&mut manager);
+        }
+        let was_finished = stats.is_finished();
+        stats.observe(&manager.drain_events());
+        if stats.is_finished() && !was_finished {
+            stats.save_json(Stats::session_file("stats"))?;
+            result = record_result(&mut records, &config, &manager, &replay, &stats)?;
+        }
+
+        write!(stdout, "{}{}", draw(&manager, mode, patterns), draw_stats(&manager, &stats))?;
+        let panel = ORIGIN.0 + 2 * manager.num_columns() as u16 + 3;
+        write!(stdout, "{}{}", cursor::Goto(panel, ORIGIN.1 + 12), result)?;
+        stdout.flush()?;
+        std::thread::sleep(Duration::from_millis(16));
+    }
+    write!(stdout, "{}{}{}{}", style::Reset, clear::All, cursor::Goto(1, 1), cursor::Show)?;
+    stdout.flush()
+}
+
+fn draw_puzzle_status(
+    puzzle: &Puzzle,
+    index: usize,
+    count: usize,
+    state: PuzzleState,
+    height: i32
+) -> String {
+    let row = ORIGIN.1 + height as u16 + 2;
+    let status = match state {
+        PuzzleState::Playing => "r retry, n/p next/previous",
+        PuzzleState::Solved => "Solved! n for the next puzzle",
+        PuzzleState::Failed => "Failed, r to retry",
+    };
+    format!(
+        "{}{}Puzzle {}/{}: {}{}{}Goal: {}{}{}",
+        cursor::Goto(1, row), clear::CurrentLine,
+        index + 1, count, puzzle.name,
+        cursor::Goto(1, row + 1), clear::CurrentLine,
+        puzzle.goal.describe(),
+        cursor::Goto(1, row + 2), status
+    )
+}
+
+/// Plays the puzzles in `dir` in order, starting at the first unsolved one.
+/// Progress is kept in a `.progress` file in the same directory.
+pub fn run_puzzles(dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let puzzles = Puzzle::load_dir(dir)?;
+    if puzzles.is_empty() {
+        return Result::Err(format!("no puzzles found in {}", dir).into())
+    }
+    let mut progress = PuzzleProgress::load(std::path::Path::new(dir).join(".progress"));
+    let mut index = puzzles.iter().position(|p| !progress.is_solved(&p.id))
+        .unwrap_or(0);
+    let config = GameConfig::default();
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock().into_raw_mode()?;
+    let mut keys = termion::async_stdin().keys();
+    let mode = ColorMode::detect();
+
+    let (mut manager, mut run) = puzzles[index].start(&config);
+    let mut last_frame = Instant::now();
+
+    write!(stdout, "{}{}", clear::All, cursor::Hide)?;
+    'game: loop {
+        for key in keys.by_ref() {
+            let restart = match key? {
+                Key::Char('q') | Key::Esc | Key::Ctrl('c') => break 'game,
+                Key::Char('r') => true,
+                Key::Char('n') => {
+                    index = (index + 1) % puzzles.len();
+                    true
+                },
+                Key::Char('p') => {
+                    index = (index + puzzles.len() - 1) % puzzles.len();
+                    true
+                },
+                key => {
+                    if run.state() == PuzzleState::Playing {
+                        if let Option::Some(action) = action_for_key(key) { manager.apply(action) }
+                    }
+                    false
+                }
+            };
+            if restart {
+                let started = puzzles[index].start(&config);
+                manager = started.0;
+                run = started.1;
+                last_frame = Instant::now();
+                write!(stdout, "{}", clear::All)?;
+            }
+        }
+
+        while last_frame.elapsed() >= FRAME {
+            if run.state() == PuzzleState::Playing {
+                manager.frame();
+            }
+            last_frame += FRAME;
+        }
+
+        let events = manager.drain_events();
+        if run.observe(&manager, &events) == PuzzleState::Solved {
+            progress.mark_solved(&puzzles[index].id)?;
+        }
+
+        write!(stdout, "{}{}", draw(&manager, mode, false), draw_puzzle_status(
+            &puzzles[index], index, puzzles.len(), run.state(), manager.num_rows()
+        ))?;
+        stdout.flush()?;
+        std::thread::sleep(Duration::from_millis(16));
+    }
+    write!(stdout, "{}{}{}{}", style::Reset, clear::All, cursor::Goto(1, 1), cursor::Show)?;
+    stdout.flush()?;
+    Result::Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_map_to_actions() {
+        let cases = [
+            (Key::Left, Action::MoveLeft),
+            (Key::Char('d'), Action::MoveRight),
+            (Key::Home, Action::DasLeft),
+            (Key::Char('D'), Action::DasRight),
+            (Key::Down, Action::SoftDrop),
+            (Key::Up, Action::RotateRight),
+            (Key::Char('x'), Action::RotateRight),
+            (Key::Char('z'), Action::RotateLeft),
+            (Key::Char('v'), Action::Rotate180),
+            (Key::Char(' '), Action::HardDrop),
+            (Key::Char('c'), Action::Hold),
+        ];
+        for (key, action) in cases {
+            assert_eq!(action_for_key(key), Option::Some(action), "{:?}", key);
+        }
+        // Quitting, skins and undo are handled by the game loop
+        for key in [Key::Char('q'), Key::Char('k'), Key::Char('u'), Key::Esc] {
+            assert_eq!(action_for_key(key), Option::None, "{:?}", key);
+        }
+    }
+
+    #[test]
+    fn mini_pieces_are_drawn_from_their_top_left_block() {
+        let piece = Piece::new(tetris::PieceType::T, Color::PURPLE, (4, 7));
+        let mut out = String::new();
+        draw_mini_piece(&mut out, ColorMode::TrueColor, &piece, (30, 5));
+        let block = |col, row| {
+            format!("{}{}", cursor::Goto(col, row), ColorMode::TrueColor.bg(&Color::PURPLE))
+        };
+        for (col, row) in [(32, 5), (30, 6), (32, 6), (34, 6)] {
+            assert!(out.contains(&block(col, row)), "no block at {}, {}", col, row);
+        }
+        assert!(!out.contains(&block(30, 5)));
+    }
+}
@@ -0,0 +1,431 @@
+use tetris::graphics::{shapes, Camera, Projection, RenderObject, Renderer};
+use tetris::{Action, Block, BlockKind, Das, Editor, GameConfig, Piece, PieceType, Skin};
+use tetris::{Record, Records, Replay, Stats, TetrisManager, FRAME, PIECE_TYPES};
+
+fn action_for_key(code: glium::glutin::VirtualKeyCode) -> Option<Action> {
+    use glium::glutin::VirtualKeyCode;
+    match code {
+        VirtualKeyCode::W | VirtualKeyCode::Up => Option::Some(Action::RotateRight),
+        VirtualKeyCode::Q => Option::Some(Action::RotateLeft),
+        VirtualKeyCode::E => Option::Some(Action::Rotate180),
+        VirtualKeyCode::A | VirtualKeyCode::Left => Option::Some(Action::MoveLeft),
+        VirtualKeyCode::D | VirtualKeyCode::Right => Option::Some(Action::MoveRight),
+        VirtualKeyCode::Home => Option::Some(Action::DasLeft),
+        VirtualKeyCode::End => Option::Some(Action::DasRight),
+        VirtualKeyCode::S | VirtualKeyCode::Down => Option::Some(Action::SoftDrop),
+        VirtualKeyCode::Space => Option::Some(Action::HardDrop),
+        VirtualKeyCode::C => Option::Some(Action::Hold),
+        _ => Option::None
+    }
+}
+
+fn piece_for_key(code: glium::glutin::VirtualKeyCode) -> Option<PieceType> {
+    use glium::glutin::VirtualKeyCode;
+    match code {
+        VirtualKeyCode::I => Option::Some(PieceType::I),
+        VirtualKeyCode::J => Option::Some(PieceType::J),
+        VirtualKeyCode::L => Option::Some(PieceType::L),
+        VirtualKeyCode::O => Option::Some(PieceType::O),
+        VirtualKeyCode::S => Option::Some(PieceType::S),
+        VirtualKeyCode::T => Option::Some(PieceType::T),
+        VirtualKeyCode::Z => Option::Some(PieceType::Z),
+        _ => Option::None
+    }
+}
+
+// The field cell under the cursor. Cells are drawn centered on
+// (0.5 - x / columns, 0.5 - y / rows) on the z = 0 plane, see `elems`
+fn cell_under(
+    camera: &mut Camera,
+    cursor: (f64, f64),
+    viewport: (f64, f64),
+    (columns, rows): (i32, i32)
+) -> Option<(i32, i32)> {
+    let point = camera.unproject_onto_plane(cursor, viewport, 0.)?;
+    let x = ((0.5 - point[0]) * columns as f32).round() as i32;
+    let y = ((0.5 - point[1]) * rows as f32).round() as i32;
+    Option::Some((x, y))
+}
+
+// The editor's blocks placed like `TetrisManager::elems`, with the cell
+// under the cursor marked when it's empty
+fn editor_elems(editor: &Editor, hovered: Option<(i32, i32)>) -> Vec<((f32,f32), Block)> {
+    let field = &editor.setup().field;
+    let (columns, rows) = (field.width(), field.height());
+    let mut cells = Vec::new();
+    for y in 0 .. rows {
+        for x in 0 .. columns {
+            match field.block_at(x, y) {
+                Option::Some(block) => cells.push(((x, y), block)),
+                Option::None if hovered == Option::Some((x, y)) => {
+                    let mark = Block { color: tetris::Color::rgb(60, 60, 60), .. Block::garbage() };
+                    cells.push(((x, y), mark))
+                },
+                Option::None => ()
+            }
+        }
+    }
+    cells.iter().map(|((x, y), block)| {
+        let f_x = -(*x as f32) / columns as f32;
+        let f_y = -(*y as f32) / rows as f32;
+        ((f_x, f_y), *block)
+    }).collect()
+}
+
+// The flat view fits the board and letterboxes the window so cells stay
+// square
+fn fit_flat(camera: &mut Camera, (columns, rows): (i32, i32)) {
+    let width = 1. + 1. / columns as f32;
+    let height = 1. + 1. / rows as f32;
+    camera.set_projection(Projection::Orthographic { width, height });
+    camera.set_letterbox(Option::Some((columns + 1) as f64 / (rows + 1) as f64));
+}
+
+// Switches between the perspective view and a flat one of a board of the
+// given size
+fn toggle_flat(camera: &mut Camera, size: (i32, i32)) {
+    match camera.projection() {
+        Projection::Perspective => fit_flat(camera, size),
+        Projection::Orthographic { .. } => {
+            camera.set_projection(Projection::Perspective);
+            camera.set_letterbox(Option::None);
+        }
+    }
+}
+
+// Refits the flat view when the window switches to a board of another size
+fn refit_flat(camera: &mut Camera, size: (i32, i32)) {
+    if camera.projection() != Projection::Perspective {
+        fit_flat(camera, size);
+    }
+}
+
+// The window title while editing, with the brush, hold and queue and a
+// note about the last edit
+fn editor_title(editor: &Editor, note: &str) -> String {
+    let setup = editor.setup();
+    let queue: String = setup.queue.iter().map(|p| p.letter()).collect();
+    let hold = setup.hold.map_or('-', |p| p.letter());
+    let brush = match editor.brush() {
+        BlockKind::Piece(piece_type) => piece_type.letter(),
+        BlockKind::Garbage => '#',
+    };
+    let title = format!("Tetris editor - brush {} hold {} queue {}", brush, hold, queue);
+    if note.is_empty() {
+        title
+    } else {
+        format!("{} - {}", title, note)
+    }
+}
+
+// Saves the setup as `setup.txt` and as fumen in `setup.fumen`, returns the
+// note to show for it
+fn export_setup(editor: &Editor) -> String {
+    if let Result::Err(e) = std::fs::write("setup.txt", editor.export()) {
+        return format!("couldn't save setup.txt: {}", e)
+    }
+    let fumen = editor.export_fumen()
+        .map_err(|e| e.to_string())
+        .and_then(|fumen| std::fs::write("setup.fumen", fumen).map_err(|e| e.to_string()));
+    match fumen {
+        Result::Ok(()) => String::from("saved setup.txt and setup.fumen"),
+        Result::Err(e) => format!("saved setup.txt, no fumen: {}", e),
+    }
+}
+
+// The window title with the personal best for the game's settings
+fn title(records: &Records, config: &GameConfig) -> String {
+    match records.personal_best(config.mode, config.config_hash(), &config.modifiers) {
+        Option::Some(best) => format!("Tetris - PB {}", config.mode.format_result(best.value)),
+        Option::None => String::from("Tetris"),
+    }
+}
+
+// Saves the result of a finished game with its replay, returns the title to
+// show for it
+fn record_result(
+    records: &mut Records,
+    config: &GameConfig,
+    manager: &TetrisManager,
+    replay: &Replay,
+    stats: &Stats
+) -> std::io::Result<String> {
+    let best = match Record::of_game(config, manager, stats.elapsed()) {
+        Option::Some(record) => records.add_with_replay(record, replay)?,
+        Option::None => false
+    };
+    if best {
+        Result::Ok(String::from("Tetris - NEW PB!"))
+    } else {
+        Result::Ok(title(records, config))
+    }
+}
+
+pub fn run() {
+    let mut events_loop = glium::glutin::EventsLoop::new();
+    let window = glium::glutin::WindowBuilder::new();
+    let context = glium::glutin::ContextBuilder::new().with_depth_buffer(24);
+    let display = glium::Display::new(window, context, &events_loop).unwrap();
+
+    let mut renderer = Renderer::new(&display).unwrap();
+    renderer.use_program("shaders/vertex.vert", "shaders/fragment.frag")
+        .unwrap();
+
+    let cube_mesh = renderer.load_mesh_with(
+        &String::from("unit_cube"),
+        &shapes::make_unit_cube
+    ).unwrap();
+
+    // let triangle_mesh =renderer.load_mesh_with(
+    //     &String::from("unit_triangle"),
+    //     &|f| { shapes::make_unit_triangle(f) }
+    // ).unwrap();
+
+    let mut camera = Camera::default();
+    camera.aspect(0.5);
+    camera.set_far(8.);
+    camera.set_near(1.);
+    camera.position(0.,0.,-2.);
+
+    let mut do_loop = true;
+
+
+    let config = GameConfig::default().width(20).height(30);
+    let mut manager = TetrisManager::with_config(&config);
+    let mut stats = Stats::new();
+    // Results and replays are kept like the terminal game's, the title shows
+    // the personal best. Games started from the editor aren't recorded
+    let mut records = Records::load("records.json").unwrap();
+    let mut replay = Replay::new(&config, &manager);
+    let mut recorded = true;
+    let mut game_title = title(&records, &config);
+    display.gl_window().set_title(&game_title);
+    // Held direction keys shift to the wall after the game's DAS
+    let mut das = Das::new();
+    // P toggles glyphs on the blocks, K cycles through the built in skins
+    // and V switches to the flat view and back
+    let mut patterns = false;
+    let mut skin_name = "guideline";
+    let mut last_frame = std::time::Instant::now();
+
+    // Tab opens the board editor. Left click paints with the brush, right
+    // click erases, B picks the next brush, F fills the row under the
+    // cursor except that cell and X clears it. Piece letters add to the
+    // queue, Backspace takes the last one off and H cycles the hold. E
+    // saves the setup and Enter starts playing it once the queue has
+    // pieces. The title shows what's being edited. The field is the
+    // standard size whatever the game's is, fumen only holds 10 wide fields
+    let mut editor = Editor::new(10, 20);
+    let mut editing = false;
+    let mut cursor = (0., 0.);
+    let mut viewport: (f64, f64) = display.gl_window().get_inner_size()
+        .map_or((800., 600.), |size| size.into());
+    let mut painting: Option<glium::glutin::MouseButton> = Option::None;
+    let mut elems = Vec::new();
+    while do_loop {
+        events_loop.poll_events(|event|{
+            if let glium::glutin::Event::WindowEvent {event, ..} = event {
+                use glium::glutin::WindowEvent;
+                use glium::glutin::ElementState;
+                use glium::glutin::MouseButton;
+                let field_size = (editor.setup().field.width(), editor.setup().field.height());
+                match event {
+                    WindowEvent::Resized(size) => {
+                        camera.aspect_of(size.into());
+                        viewport = size.into();
+                    },
+                    WindowEvent::CursorMoved{position, ..} => {
+                        cursor = position.into();
+                        if !editing {
+                            return
+                        }
+                        let cell = cell_under(&mut camera, cursor, viewport, field_size);
+                        match (painting, cell) {
+                            (Option::Some(MouseButton::Left), Option::Some((x, y))) => {
+                                editor.paint(x, y)
+                            },
+                            (Option::Some(MouseButton::Right), Option::Some((x, y))) => {
+                                editor.erase(x, y)
+                            },
+                            _ => ()
+                        }
+                    },
+                    WindowEvent::MouseInput{state, button, ..} if editing => {
+                        if state == ElementState::Released {
+                            painting = Option::None;
+                            return
+                        }
+                        if let Option::Some((x, y)) = cell_under(&mut camera, cursor, viewport, field_size) {
+                            match button {
+                                MouseButton::Left => editor.paint(x, y),
+                                MouseButton::Right => editor.erase(x, y),
+                                _ => ()
+                            }
+                            painting = Option::Some(button);
+                        }
+                    },
+                    WindowEvent::KeyboardInput{input,..} if editing => {
+                        use glium::glutin::VirtualKeyCode;
+                        if input.state != ElementState::Pressed {
+                            return
+                        }
+                        let cell = cell_under(&mut camera, cursor, viewport, field_size);
+                        let game_size = (manager.num_columns(), manager.num_rows());
+                        let mut note = String::new();
+                        match input.virtual_keycode {
+                            Option::Some(VirtualKeyCode::Tab) => {
+                                editing = false;
+                                refit_flat(&mut camera, game_size);
+                                display.gl_window().set_title(&game_title);
+                                return
+                            },
+                            Option::Some(VirtualKeyCode::Return) => match editor.start(&config) {
+                                Option::Some(game) => {
+                                    manager = game;
+                                    replay = Replay::new(&config, &manager);
+                                    recorded = false;
+                                    stats = Stats::new();
+                                    editing = false;
+                                    let size = (manager.num_columns(), manager.num_rows());
+                                    refit_flat(&mut camera, size);
+                                    game_title = String::from("Tetris - setup");
+                                    display.gl_window().set_title(&game_title);
+                                    return
+                                },
+                                Option::None => {
+                                    note = String::from("add pieces to the queue first")
+                                },
+                            },
+                            Option::Some(VirtualKeyCode::V) => toggle_flat(&mut camera, field_size),
+                            Option::Some(VirtualKeyCode::B) => editor.next_brush(),
+                            Option::Some(VirtualKeyCode::F) => {
+                                if let Option::Some((x, y)) = cell {
+                                    editor.fill_row(y, x);
+                                }
+                            },
+                            Option::Some(VirtualKeyCode::X) => {
+                                if let Option::Some((_, y)) = cell {
+                                    editor.clear_row(y);
+                                }
+                            },
+                            Option::Some(VirtualKeyCode::Back) => { editor.pop_queue(); },
+                            Option::Some(VirtualKeyCode::H) => {
+                                let next = match editor.setup().hold {
+                                    Option::None => Option::Some(PIECE_TYPES[0]),
+                                    Option::Some(held) => PIECE_TYPES.iter()
+                                        .skip_while(|p| **p != held)
+                                        .nth(1).cloned(),
+                                };
+                                editor.set_hold(next);
+                            },
+                            Option::Some(VirtualKeyCode::E) => note = export_setup(&editor),
+                            code => match code.and_then(piece_for_key) {
+                                Option::Some(piece_type) => editor.push_queue(piece_type),
+                                Option::None => return
+                            }
+                        }
+                        display.gl_window().set_title(&editor_title(&editor, &note));
+                    },
+                    WindowEvent::KeyboardInput{input,..}
+                        if input.state == ElementState::Pressed => {
+                            use glium::glutin::VirtualKeyCode;
+                            match input.virtual_keycode {
+                                Option::Some(VirtualKeyCode::Tab) => {
+                                    editing = true;
+                                    refit_flat(&mut camera, field_size);
+                                    display.gl_window().set_title(&editor_title(&editor, ""));
+                                },
+                                Option::Some(VirtualKeyCode::P) => patterns = !patterns,
+                                Option::Some(VirtualKeyCode::V) => {
+                                    toggle_flat(&mut camera, (manager.num_columns(), manager.num_rows()));
+                                },
+                                Option::Some(VirtualKeyCode::K) => {
+                                    skin_name = Skin::next_preset(skin_name);
+                                    manager.set_skin(Skin::preset(skin_name).unwrap());
+                                },
+                                code => {
+                                    let action = code.and_then(action_for_key)
+                                        .and_then(|action| das.press(action));
+                                    if let Option::Some(action) = action {
+                                        replay.apply(&mut manager, action)
+                                    }
+                                }
+                            }
+                        },
+                    WindowEvent::KeyboardInput{input,..} => {
+                        if let Option::Some(action) = input.virtual_keycode.and_then(action_for_key) {
+                            das.release(action)
+                        }
+                    },
+                    WindowEvent::CloseRequested => {
+                        println!("Got break request!");
+                        do_loop = false;
+                    },
+                    _ => ()
+                }
+            }
+        });
+
+
+        while last_frame.elapsed() >= FRAME {
+            if !editing {
+                // A charged key pushes every frame, only a push that moves
+                // the piece is applied so it counts as a single key press
+                if let Option::Some(shift) = das.frame(manager.timing().das) {
+                    let step = match shift {
+                        Action::DasLeft => Piece::move_left,
+                        _ => Piece::move_right,
+                    };
+                    let moves = manager.active()
+                        .is_some_and(|piece| manager.field().valid_piece(step(&piece)));
+                    if moves {
+                        replay.apply(&mut manager, shift);
+                    }
+                }
+                replay.frame(&mut manager);
+            }
+            last_frame += FRAME;
+        }
+        let was_finished = stats.is_finished();
+        stats.observe(&manager.drain_events());
+        if stats.is_finished() && !was_finished {
+            if let Result::Err(e) = stats.save_json(Stats::session_file("stats")) {
+                println!("Couldn't save statistics: {}", e);
+            }
+            if recorded {
+                game_title = match record_result(&mut records, &config, &manager, &replay, &stats) {
+                    Result::Ok(title) => title,
+                    Result::Err(e) => format!("Tetris - couldn't save the result: {}", e),
+                };
+                if !editing {
+                    display.gl_window().set_title(&game_title);
+                }
+            }
+        }
+
+        let ((columns, rows), nodes) = if editing {
+            let field_size = (editor.setup().field.width(), editor.setup().field.height());
+            let hovered = cell_under(&mut camera, cursor, viewport, field_size);
+            (field_size, editor_elems(&editor, hovered))
+        } else {
+            ((manager.num_columns(), manager.num_rows()), manager.elems())
+        };
+        let scale = 0.5 / columns as f32  - 0.0001;
+        let scale_y = 0.5 / rows as f32  - 0.0001;
+        elems.clear();
+        elems.extend(nodes.iter().map(|((x,y), block)| {
+            let mut obj = RenderObject::new(cube_mesh);
+            let (r,g,b) = (&block.color).into();
+            if patterns {
+                obj.pattern(block.kind.glyph() as i32);
+            }
+            obj.trans().move_to(*x, *y, 0.);
+            obj.trans().add_position(0.5, 0.5, 0.);
+            obj.trans().set_scale(scale, scale_y, scale);
+            obj.rgb(r,g,b);
+            obj
+        }));
+
+        renderer.render(&mut elems, &mut camera);
+    }
+}